@@ -0,0 +1,129 @@
+/*
+ * C ABI of phasetida-core, matching `src/ffi.rs`. The declarations, constants
+ * and struct layouts are checked against the crate by `tests/ffi.rs`.
+ *
+ * Every function operates on a thread-local engine, so all calls must be made
+ * from the same thread.
+ */
+
+#ifndef PHASETIDA_CORE_H
+#define PHASETIDA_CORE_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef int32_t PhasetidaStatus;
+
+#define PHASETIDA_OK 0
+#define PHASETIDA_NULL_POINTER 1
+#define PHASETIDA_INVALID_UTF8 2
+#define PHASETIDA_INVALID_JSON 3
+#define PHASETIDA_UNSUPPORTED_VERSION 4
+#define PHASETIDA_INVALID_CHART 5
+#define PHASETIDA_INVALID_PEC 6
+#define PHASETIDA_BUFFER_TOO_SMALL 7
+#define PHASETIDA_PANIC 8
+#define PHASETIDA_INVALID_ARGUMENT 9
+
+#define PHASETIDA_SEEK_PRACTICE 0
+#define PHASETIDA_SEEK_AUTOPLAY_FILL 1
+#define PHASETIDA_SEEK_RESET 2
+
+#define PHASETIDA_PLAY_MODE_NORMAL 0
+#define PHASETIDA_PLAY_MODE_SUDDEN_DEATH 1
+#define PHASETIDA_PLAY_MODE_PERFECT_ONLY 2
+#define PHASETIDA_PLAY_MODE_NO_FAIL 3
+
+/*
+ * A rendered frame starts with PhasetidaFrameHeader, followed by the records
+ * and a terminating 0 byte. See RENDER_SCHEMA in the crate for the layout of
+ * every record.
+ */
+#define PHASETIDA_RENDER_MAGIC "PTDF"
+#define PHASETIDA_RENDER_PROTOCOL_VERSION 4
+#define PHASETIDA_RECORD_TYPE_COUNT 7
+
+#pragma pack(push, 1)
+typedef struct PhasetidaFrameHeader {
+    uint8_t magic[4];
+    uint16_t version;
+    /* 0 for little endian, 1 for big endian */
+    uint8_t endianness;
+    double frame_time;
+    /* counts[i] is the number of records of type i + 1 */
+    uint32_t counts[PHASETIDA_RECORD_TYPE_COUNT];
+} PhasetidaFrameHeader;
+#pragma pack(pop)
+
+typedef struct PhasetidaMetadata {
+    double length_in_second;
+    double offset;
+    int32_t format_version;
+} PhasetidaMetadata;
+
+/*
+ * Load a chart from a UTF-8 buffer, replacing the current one. The chart is
+ * read as JSON if it starts with `{`, and as a PhiEdit chart otherwise. A
+ * leading UTF-8 byte order mark is skipped. `out_metadata` may be NULL.
+ */
+PhasetidaStatus phasetida_load_chart(const uint8_t *data, size_t len,
+                                     PhasetidaMetadata *out_metadata);
+
+PhasetidaStatus phasetida_load_image_offset(double hold_head_height,
+                                            double hold_head_highlight_height,
+                                            double hold_end_height,
+                                            double hold_end_highlight_height);
+
+PhasetidaStatus phasetida_tick(double time_in_second,
+                               double delta_time_in_second, bool autoplay);
+
+PhasetidaStatus phasetida_touch_down(size_t id, float x, float y);
+
+PhasetidaStatus phasetida_touch_move(size_t id, float x, float y);
+
+PhasetidaStatus phasetida_touch_up(size_t id);
+
+PhasetidaStatus phasetida_reset(double before_time_in_second);
+
+PhasetidaStatus phasetida_seek(double time_in_second, int32_t policy);
+
+PhasetidaStatus phasetida_set_play_mode(int32_t mode);
+
+/*
+ * Override the colour of a judge line. By default lines are tinted gold while
+ * all perfect, blue while full combo and white otherwise.
+ */
+PhasetidaStatus phasetida_set_line_color(size_t line, uint8_t r, uint8_t g,
+                                         uint8_t b);
+
+PhasetidaStatus phasetida_reset_line_color(size_t line);
+
+PhasetidaStatus phasetida_clear(void);
+
+/*
+ * Render the states into `buf`. The length of the frame is always written to
+ * `out_len`. If it is larger than `capacity`, nothing is written to `buf` and
+ * PHASETIDA_BUFFER_TOO_SMALL is returned.
+ */
+PhasetidaStatus phasetida_render(uint8_t *buf, size_t capacity,
+                                 size_t *out_len);
+
+uint16_t phasetida_render_protocol_version(void);
+
+/*
+ * Copy the UTF-8 message of the last error into `buf`, without a trailing
+ * nul. Follows the same buffer rules as phasetida_render.
+ */
+PhasetidaStatus phasetida_last_error(uint8_t *buf, size_t capacity,
+                                     size_t *out_len);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* PHASETIDA_CORE_H */
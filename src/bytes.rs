@@ -0,0 +1,59 @@
+/// A bounds-checked reader of packed little or big endian fields, shared by
+/// the decoders of the render stream and of replays
+pub(crate) struct ByteReader<'a> {
+    pub bytes: &'a [u8],
+    pub offset: usize,
+    pub big_endian: bool,
+}
+
+impl<'a> ByteReader<'a> {
+    /// Create a little endian reader at the start of `bytes`
+    pub fn new(bytes: &'a [u8]) -> ByteReader<'a> {
+        ByteReader {
+            bytes,
+            offset: 0,
+            big_endian: false,
+        }
+    }
+
+    /// Whether every byte has been read
+    pub fn is_finished(&self) -> bool {
+        self.offset >= self.bytes.len()
+    }
+
+    /// Read `N` bytes, in native byte order
+    pub fn take<const N: usize>(&mut self) -> Option<[u8; N]> {
+        let slice = self.bytes.get(self.offset..self.offset + N)?;
+        self.offset += N;
+        let mut array = [0; N];
+        array.copy_from_slice(slice);
+        if self.big_endian != cfg!(target_endian = "big") {
+            array.reverse();
+        }
+        Some(array)
+    }
+
+    pub fn u8(&mut self) -> Option<u8> {
+        self.take::<1>().map(u8::from_ne_bytes)
+    }
+
+    pub fn i8(&mut self) -> Option<i8> {
+        self.take::<1>().map(i8::from_ne_bytes)
+    }
+
+    pub fn u16(&mut self) -> Option<u16> {
+        self.take::<2>().map(u16::from_ne_bytes)
+    }
+
+    pub fn u32(&mut self) -> Option<u32> {
+        self.take::<4>().map(u32::from_ne_bytes)
+    }
+
+    pub fn f32(&mut self) -> Option<f32> {
+        self.take::<4>().map(f32::from_ne_bytes)
+    }
+
+    pub fn f64(&mut self) -> Option<f64> {
+        self.take::<8>().map(f64::from_ne_bytes)
+    }
+}
@@ -1,4 +1,9 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    chart_pec::ChartPec, chart_rpe::ChartRpe, chart_validation::Diagnostic, error::ChartLoadError,
+    states::get_seconds_per_tick, states_lines,
+};
 
 /// The raw chart format
 #[derive(Clone)]
@@ -8,9 +13,39 @@ pub enum ChartRaw {
 
     /// The new(standard) format of chart
     V3(Chart),
+
+    /// The format of Re:PhiEdit, converted to the standard format on loading
+    Rpe(ChartRpe),
+
+    /// The text format of `PhiEdit`, converted to the standard format on loading
+    Pec(ChartPec),
+}
+
+/// The features of a chart that are lost when converting it to the standard
+/// format
+#[derive(Serialize, Clone, Default)]
+pub struct ConversionReport {
+    /// The lost features, in the order they were found
+    pub issues: Vec<ConversionIssue>,
+}
+
+/// A feature of a chart that can not be expressed by the standard format
+#[derive(Serialize, Clone)]
+pub struct ConversionIssue {
+    /// The index of the judge line, if the issue belongs to a line
+    pub line: Option<usize>,
+
+    /// The index of the note in the line, if the issue belongs to a note
+    pub note: Option<usize>,
+
+    /// What was lost
+    pub message: String,
 }
 
 /// The standard(v3) format of chart
+///
+/// Serializing the chart writes the official v3 json, including
+/// `formatVersion` and the note counts of the chart and of every line.
 #[derive(Deserialize, Clone)]
 pub struct Chart {
     /// The offset of the chart, in seconds. If the value is greater than 0,
@@ -66,11 +101,19 @@ pub struct JudgeLineV1 {
     pub alpha_events: Vec<Event2>,
 }
 
-#[derive(Copy, Clone, PartialEq, Eq)]
+/// The type of a note
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub enum NoteType {
+    /// A tap note, type 1
     Tap,
+
+    /// A drag note, type 2
     Drag,
+
+    /// A hold note, type 3
     Hold,
+
+    /// A flick note, type 4
     Flick,
 }
 
@@ -99,6 +142,15 @@ impl From<NoteType> for i8 {
     }
 }
 
+impl Serialize for NoteType {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_i8((*self).into())
+    }
+}
+
 impl<'de> Deserialize<'de> for NoteType {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
@@ -106,12 +158,15 @@ impl<'de> Deserialize<'de> for NoteType {
     {
         let i = i32::deserialize(deserializer)?;
         Self::try_from(i).map_err(|()| {
-            serde::de::Error::invalid_value(serde::de::Unexpected::Signed(0), &"1, 2, 3 or 4")
+            serde::de::Error::invalid_value(
+                serde::de::Unexpected::Signed(i64::from(i)),
+                &"1, 2, 3 or 4",
+            )
         })
     }
 }
 
-#[derive(Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Note {
     #[serde(rename = "type")]
     pub r#type: NoteType,
@@ -125,7 +180,7 @@ pub struct Note {
     pub floor_position: f64,
 }
 
-#[derive(Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Event1 {
     #[serde(rename = "startTime")]
     pub start_time: f64,
@@ -144,7 +199,7 @@ pub struct Event2 {
     pub end: f64,
 }
 
-#[derive(Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Event4 {
     #[serde(rename = "startTime")]
     pub start_time: f64,
@@ -237,35 +292,236 @@ impl WithTimeRange for Event4 {
     }
 }
 
+impl Serialize for Chart {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use serde::ser::SerializeStruct;
+        let mut state = serializer.serialize_struct("Chart", 4)?;
+        state.serialize_field("formatVersion", &3)?;
+        state.serialize_field("offset", &self.offset)?;
+        state.serialize_field(
+            "numOfNotes",
+            &self
+                .judge_line_list
+                .iter()
+                .map(|it| it.notes_above.len() + it.notes_below.len())
+                .sum::<usize>(),
+        )?;
+        state.serialize_field("judgeLineList", &self.judge_line_list)?;
+        state.end()
+    }
+}
+
+impl Serialize for JudgeLine {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use serde::ser::SerializeStruct;
+        let mut state = serializer.serialize_struct("JudgeLine", 10)?;
+        state.serialize_field(
+            "numOfNotes",
+            &(self.notes_above.len() + self.notes_below.len()),
+        )?;
+        state.serialize_field("numOfNotesAbove", &self.notes_above.len())?;
+        state.serialize_field("numOfNotesBelow", &self.notes_below.len())?;
+        state.serialize_field("bpm", &self.bpm)?;
+        state.serialize_field("notesAbove", &self.notes_above)?;
+        state.serialize_field("notesBelow", &self.notes_below)?;
+        let seconds_per_tick = get_seconds_per_tick(self.bpm);
+        state.serialize_field(
+            "speedEvents",
+            &self
+                .speed_events
+                .iter()
+                .map(|it| SpeedEventV3 {
+                    event: it,
+                    floor_position: states_lines::get_floor_position(
+                        &self.speed_events,
+                        seconds_per_tick,
+                        it.start_time,
+                    ),
+                })
+                .collect::<Vec<_>>(),
+        )?;
+        state.serialize_field("judgeLineMoveEvents", &self.move_events)?;
+        state.serialize_field("judgeLineRotateEvents", &self.rotate_events)?;
+        state.serialize_field("judgeLineDisappearEvents", &self.alpha_events)?;
+        state.end()
+    }
+}
+
+/// A speed event with the floor position of the line at its start, as
+/// written in official v3 json
+struct SpeedEventV3<'a> {
+    event: &'a Event1,
+    floor_position: f64,
+}
+
+impl Serialize for SpeedEventV3<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use serde::ser::SerializeStruct;
+        let mut state = serializer.serialize_struct("Event1", 4)?;
+        state.serialize_field("startTime", &self.event.start_time)?;
+        state.serialize_field("endTime", &self.event.end_time)?;
+        state.serialize_field("value", &self.event.value)?;
+        state.serialize_field("floorPosition", &self.floor_position)?;
+        state.end()
+    }
+}
+
+impl Serialize for Event2 {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use serde::ser::SerializeStruct;
+        let mut state = serializer.serialize_struct("Event2", 6)?;
+        state.serialize_field("startTime", &self.start_time)?;
+        state.serialize_field("endTime", &self.end_time)?;
+        state.serialize_field("start", &self.start)?;
+        state.serialize_field("end", &self.end)?;
+        state.serialize_field("start2", &0.0)?;
+        state.serialize_field("end2", &0.0)?;
+        state.end()
+    }
+}
+
+impl Chart {
+    /// Write the chart as official v3 json.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the serialization failed.
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string(self)
+    }
+}
+
 impl<'de> Deserialize<'de> for ChartRaw {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: serde::Deserializer<'de>,
     {
         let value = serde_json::Value::deserialize(deserializer)?;
+        ChartRaw::from_value(value).map_err(serde::de::Error::custom)
+    }
+}
+
+impl ChartRaw {
+    /// Parse a chart of any supported version from raw json.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the json is malformed, the
+    /// version is not supported, or the chart contains invalid values.
+    pub fn from_json(json: &str) -> Result<ChartRaw, ChartLoadError> {
+        ChartRaw::from_value(serde_json::from_str(json)?)
+    }
+
+    /// Parse a chart from the text of `PhiEdit`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the text can not be parsed.
+    pub fn from_pec(text: &str) -> Result<ChartRaw, ChartLoadError> {
+        Ok(ChartRaw::Pec(ChartPec::parse(text)?))
+    }
+
+    /// Parse a chart of any supported version from a json value.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the value is not a chart, the
+    /// version is not supported, or the chart contains invalid values.
+    pub fn from_value(value: serde_json::Value) -> Result<ChartRaw, ChartLoadError> {
+        if value.get("formatVersion").is_none()
+            && value.get("META").is_some()
+            && value.get("BPMList").is_some()
+        {
+            // unknown note types of RPE are dropped and reported when converting
+            let chart_raw = ChartRaw::Rpe(serde_json::from_value::<ChartRpe>(value)?);
+            chart_raw.check_bpm()?;
+            return Ok(chart_raw);
+        }
         let version = value
             .get("formatVersion")
             .and_then(serde_json::Value::as_i64)
-            .ok_or(serde::de::Error::missing_field("formatVersion"))?;
-        match version {
-            1 => Ok(ChartRaw::V1(
-                serde_json::from_value::<ChartV1>(value).map_err(serde::de::Error::custom)?,
-            )),
-            3 => Ok(ChartRaw::V3(
-                serde_json::from_value::<Chart>(value).map_err(serde::de::Error::custom)?,
-            )),
-            _ => Err(serde::de::Error::custom(format!(
-                "unknown version: {version}"
-            ))),
+            .ok_or(ChartLoadError::MissingFormatVersion)?;
+        if version != 1 && version != 3 {
+            return Err(ChartLoadError::UnsupportedVersion(version));
+        }
+        check_note_types(&value, &["notesAbove", "notesBelow"])?;
+        let chart_raw = match version {
+            1 => ChartRaw::V1(serde_json::from_value::<ChartV1>(value)?),
+            _ => ChartRaw::V3(serde_json::from_value::<Chart>(value)?),
+        };
+        chart_raw.check_bpm()?;
+        Ok(chart_raw)
+    }
+
+    fn check_bpm(&self) -> Result<(), ChartLoadError> {
+        let bpm_list: Vec<f64> = match self {
+            ChartRaw::V1(v1) => v1.judge_line_list.iter().map(|it| it.bpm).collect(),
+            ChartRaw::V3(v3) => v3.judge_line_list.iter().map(|it| it.bpm).collect(),
+            ChartRaw::Rpe(rpe) => {
+                if rpe.bpm_list.is_empty() {
+                    return Err(ChartLoadError::Validation(vec![Diagnostic::chart_error(
+                        "`BPMList` is empty".to_string(),
+                    )]));
+                }
+                if let Some((i, it)) = rpe
+                    .bpm_list
+                    .iter()
+                    .enumerate()
+                    .find(|(_, it)| it.bpm.is_nan() || it.bpm <= 0.0)
+                {
+                    return Err(ChartLoadError::Validation(vec![Diagnostic::chart_error(
+                        format!("`BPMList[{i}]` has a non-positive bpm: {}", it.bpm),
+                    )]));
+                }
+                return Ok(());
+            }
+            ChartRaw::Pec(_) => return Ok(()),
+        };
+        match bpm_list
+            .into_iter()
+            .enumerate()
+            .find(|(_, bpm)| bpm.is_nan() || *bpm <= 0.0)
+        {
+            Some((line, bpm)) => Err(ChartLoadError::NonPositiveBpm { line, bpm }),
+            None => Ok(()),
+        }
+    }
+
+    /// The format version of the chart, the `RPEVersion` of an RPE chart, or
+    /// 0 for a PEC chart
+    #[must_use]
+    pub fn format_version(&self) -> i32 {
+        match self {
+            ChartRaw::V1(_) => 1,
+            ChartRaw::V3(_) => 3,
+            ChartRaw::Rpe(rpe) => rpe.meta.rpe_version,
+            ChartRaw::Pec(_) => 0,
         }
     }
-}
 
-impl ChartRaw {
     /// Convert any chart to standard v3 format
     #[must_use]
     pub fn convert_to_v3(self) -> Chart {
-        match self {
+        self.convert_to_v3_with_report().0
+    }
+
+    /// Convert any chart to standard v3 format, reporting the features that
+    /// are lost in the conversion
+    #[must_use]
+    pub fn convert_to_v3_with_report(self) -> (Chart, ConversionReport) {
+        let chart = match self {
             ChartRaw::V1(v1) => Chart {
                 offset: v1.offset,
                 judge_line_list: v1
@@ -275,7 +531,10 @@ impl ChartRaw {
                     .collect(),
             },
             ChartRaw::V3(v3) => v3,
-        }
+            ChartRaw::Rpe(rpe) => return rpe.convert_to_v3(),
+            ChartRaw::Pec(pec) => return pec.convert_to_v3(),
+        };
+        (chart, ConversionReport::default())
     }
 }
 
@@ -310,3 +569,35 @@ impl From<JudgeLineV1> for JudgeLine {
         }
     }
 }
+
+fn check_note_types(value: &serde_json::Value, sides: &[&str]) -> Result<(), ChartLoadError> {
+    let Some(lines) = value
+        .get("judgeLineList")
+        .and_then(serde_json::Value::as_array)
+    else {
+        return Ok(());
+    };
+    for (i, line) in lines.iter().enumerate() {
+        for side in sides {
+            let Some(notes) = line.get(side).and_then(serde_json::Value::as_array) else {
+                continue;
+            };
+            for (j, note) in notes.iter().enumerate() {
+                let Some(note_type) = note.get("type") else {
+                    continue;
+                };
+                let valid = note_type
+                    .as_i64()
+                    .and_then(|it| i32::try_from(it).ok())
+                    .is_some_and(|it| NoteType::try_from(it).is_ok());
+                if !valid {
+                    return Err(ChartLoadError::InvalidNoteType {
+                        value: note_type.clone(),
+                        path: format!("judgeLineList[{i}].{side}[{j}]"),
+                    });
+                }
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,359 @@
+//! Bakes the eased, layered events of fan-made chart formats into the linear
+//! events of the official format.
+
+use crate::{
+    chart::{Event1, Event2, Event4},
+    math,
+};
+
+const EVENT_BEGIN: f64 = -999_999.0;
+const EVENT_END: f64 = 1_000_000_000.0;
+const STEPS_PER_BEAT: f64 = 16.0;
+const MAX_STEPS_PER_SEGMENT: f64 = 1024.0;
+const EPSILON: f64 = 1e-9;
+
+/// A list of BPM changes, used to map beats to ticks of the first BPM
+pub(crate) struct BpmList {
+    /// `(start_beat, bpm, start_second)`, sorted by beat
+    segments: Vec<(f64, f64, f64)>,
+}
+
+impl BpmList {
+    /// Create the list from `(start_beat, bpm)` pairs. The pairs must not be
+    /// empty and every BPM must be positive.
+    pub(crate) fn new(mut points: Vec<(f64, f64)>) -> BpmList {
+        points.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let mut segments = Vec::<(f64, f64, f64)>::with_capacity(points.len());
+        for (beat, bpm) in points {
+            let second = segments
+                .last()
+                .map_or(0.0, |&(last_beat, last_bpm, last_second)| {
+                    last_second + (beat - last_beat) * 60.0 / last_bpm
+                });
+            segments.push((beat, bpm, second));
+        }
+        BpmList { segments }
+    }
+
+    /// The BPM that every converted judge line uses
+    pub(crate) fn base_bpm(&self) -> f64 {
+        self.segments.first().map_or(120.0, |it| it.1)
+    }
+
+    pub(crate) fn seconds(&self, beat: f64) -> f64 {
+        let segment = self
+            .segments
+            .iter()
+            .rev()
+            .find(|it| it.0 <= beat)
+            .or(self.segments.first());
+        match segment {
+            Some(&(start_beat, bpm, start_second)) => {
+                start_second + (beat - start_beat) * 60.0 / bpm
+            }
+            None => beat * 60.0 / self.base_bpm(),
+        }
+    }
+
+    /// Convert the beat to ticks of the base BPM
+    pub(crate) fn tick(&self, beat: f64) -> f64 {
+        self.seconds(beat) * self.base_bpm() * 32.0 / 60.0
+    }
+
+    fn change_beats(&self) -> impl Iterator<Item = f64> + '_ {
+        self.segments.iter().skip(1).map(|it| it.0)
+    }
+}
+
+/// The easing of a curve, optionally clipped to `[left, right]`
+#[derive(Clone, Copy)]
+pub(crate) struct Easing {
+    pub kind: i32,
+    pub left: f64,
+    pub right: f64,
+}
+
+impl Easing {
+    pub(crate) fn is_supported(kind: i32) -> bool {
+        math::ease(kind, 0.0).is_some()
+    }
+
+    fn is_linear(self) -> bool {
+        self.kind <= 1 || !Easing::is_supported(self.kind)
+    }
+
+    fn apply(self, x: f64) -> f64 {
+        let f = |x: f64| math::ease(self.kind, x).unwrap_or(x);
+        if self.is_linear() {
+            return x;
+        }
+        let low = f(self.left);
+        let high = f(self.right);
+        if (high - low).abs() <= EPSILON {
+            return x;
+        }
+        (f(self.left + (self.right - self.left) * x) - low) / (high - low)
+    }
+}
+
+/// A value changing from `start` to `end` between two beats
+#[derive(Clone, Copy)]
+pub(crate) struct Curve {
+    pub start_beat: f64,
+    pub end_beat: f64,
+    pub start: f64,
+    pub end: f64,
+    pub easing: Easing,
+}
+
+impl Curve {
+    pub(crate) fn value_at(&self, beat: f64) -> f64 {
+        let length = self.end_beat - self.start_beat;
+        if length <= EPSILON {
+            return self.end;
+        }
+        let percent = ((beat - self.start_beat) / length).clamp(0.0, 1.0);
+        self.start + (self.end - self.start) * self.easing.apply(percent)
+    }
+
+    fn is_constant(&self) -> bool {
+        (self.end - self.start).abs() <= EPSILON
+    }
+}
+
+/// What a layer looks like inside a segment between two adjacent breakpoints
+enum Shape<'a> {
+    Constant(f64),
+    Curve(&'a Curve),
+}
+
+fn shape_in_segment(layer: &[Curve], from: f64, to: f64) -> Shape<'_> {
+    if let Some(curve) = layer
+        .iter()
+        .find(|it| it.start_beat <= from + EPSILON && to <= it.end_beat + EPSILON)
+    {
+        if curve.is_constant() {
+            return Shape::Constant(curve.start);
+        }
+        return Shape::Curve(curve);
+    }
+    let before = layer
+        .iter()
+        .filter(|it| it.end_beat <= from + EPSILON)
+        .max_by(|a, b| a.end_beat.total_cmp(&b.end_beat));
+    match (before, layer.first()) {
+        (Some(curve), _) => Shape::Constant(curve.end),
+        (None, Some(curve)) => Shape::Constant(curve.start),
+        (None, None) => Shape::Constant(0.0),
+    }
+}
+
+fn sample(layers: &[Vec<Curve>], from: f64, to: f64, beat: f64) -> f64 {
+    layers
+        .iter()
+        .map(|layer| match shape_in_segment(layer, from, to) {
+            Shape::Constant(value) => value,
+            Shape::Curve(curve) => curve.value_at(beat),
+        })
+        .sum()
+}
+
+fn is_linear(layers: &[Vec<Curve>], from: f64, to: f64) -> bool {
+    layers
+        .iter()
+        .all(|layer| match shape_in_segment(layer, from, to) {
+            Shape::Constant(_) => true,
+            Shape::Curve(curve) => curve.easing.is_linear(),
+        })
+}
+
+fn is_constant(layers: &[Vec<Curve>], from: f64, to: f64) -> bool {
+    layers
+        .iter()
+        .all(|layer| matches!(shape_in_segment(layer, from, to), Shape::Constant(_)))
+}
+
+fn breakpoints(channels: &[&[Vec<Curve>]], bpm_list: &BpmList) -> Vec<f64> {
+    let mut points = channels
+        .iter()
+        .flat_map(|layers| layers.iter())
+        .flat_map(|layer| layer.iter())
+        .flat_map(|curve| [curve.start_beat, curve.end_beat])
+        .collect::<Vec<_>>();
+    if let (Some(first), Some(last)) = (
+        points.iter().copied().reduce(f64::min),
+        points.iter().copied().reduce(f64::max),
+    ) {
+        points.extend(
+            bpm_list
+                .change_beats()
+                .filter(|it| first < *it && *it < last),
+        );
+    }
+    points.sort_by(f64::total_cmp);
+    points.dedup_by(|a, b| (*a - *b).abs() <= EPSILON);
+    points
+}
+
+/// Split the segment into sub-segments that are linear enough to be expressed
+#[allow(clippy::cast_sign_loss)]
+fn steps(linear: bool, from: f64, to: f64) -> Vec<(f64, f64)> {
+    let count = if linear {
+        1.0
+    } else {
+        ((to - from) * STEPS_PER_BEAT)
+            .ceil()
+            .clamp(1.0, MAX_STEPS_PER_SEGMENT)
+    };
+    let count = count as u32;
+    (0..count)
+        .map(|i| {
+            let a = from + (to - from) * f64::from(i) / f64::from(count);
+            let b = from + (to - from) * f64::from(i + 1) / f64::from(count);
+            (a, b)
+        })
+        .collect()
+}
+
+/// Bake the sum of the layers into official events with linear interpolation
+pub(crate) fn bake_events(layers: &[Vec<Curve>], bpm_list: &BpmList) -> Vec<Event2> {
+    let points = breakpoints(&[layers], bpm_list);
+    let (Some(&first), Some(&last)) = (points.first(), points.last()) else {
+        let value = sample(layers, 0.0, 0.0, 0.0);
+        return vec![Event2 {
+            start_time: EVENT_BEGIN,
+            end_time: EVENT_END,
+            start: value,
+            end: value,
+        }];
+    };
+    let head = sample(layers, f64::NEG_INFINITY, first, first);
+    let mut events = vec![Event2 {
+        start_time: EVENT_BEGIN,
+        end_time: bpm_list.tick(first),
+        start: head,
+        end: head,
+    }];
+    for window in points.windows(2) {
+        let (from, to) = (window[0], window[1]);
+        for (a, b) in steps(is_linear(layers, from, to), from, to) {
+            events.push(Event2 {
+                start_time: bpm_list.tick(a),
+                end_time: bpm_list.tick(b),
+                start: sample(layers, from, to, a),
+                end: sample(layers, from, to, b),
+            });
+        }
+    }
+    let tail = sample(layers, last, f64::INFINITY, last);
+    events.push(Event2 {
+        start_time: bpm_list.tick(last),
+        end_time: EVENT_END,
+        start: tail,
+        end: tail,
+    });
+    events
+}
+
+/// Bake two channels into official move events
+pub(crate) fn bake_move_events(
+    x_layers: &[Vec<Curve>],
+    y_layers: &[Vec<Curve>],
+    bpm_list: &BpmList,
+) -> Vec<Event4> {
+    let points = breakpoints(&[x_layers, y_layers], bpm_list);
+    let (Some(&first), Some(&last)) = (points.first(), points.last()) else {
+        let x = sample(x_layers, 0.0, 0.0, 0.0);
+        let y = sample(y_layers, 0.0, 0.0, 0.0);
+        return vec![Event4 {
+            start_time: EVENT_BEGIN,
+            end_time: EVENT_END,
+            start: x,
+            end: x,
+            start2: y,
+            end2: y,
+        }];
+    };
+    let head_x = sample(x_layers, f64::NEG_INFINITY, first, first);
+    let head_y = sample(y_layers, f64::NEG_INFINITY, first, first);
+    let mut events = vec![Event4 {
+        start_time: EVENT_BEGIN,
+        end_time: bpm_list.tick(first),
+        start: head_x,
+        end: head_x,
+        start2: head_y,
+        end2: head_y,
+    }];
+    for window in points.windows(2) {
+        let (from, to) = (window[0], window[1]);
+        let linear = is_linear(x_layers, from, to) && is_linear(y_layers, from, to);
+        for (a, b) in steps(linear, from, to) {
+            events.push(Event4 {
+                start_time: bpm_list.tick(a),
+                end_time: bpm_list.tick(b),
+                start: sample(x_layers, from, to, a),
+                end: sample(x_layers, from, to, b),
+                start2: sample(y_layers, from, to, a),
+                end2: sample(y_layers, from, to, b),
+            });
+        }
+    }
+    let tail_x = sample(x_layers, last, f64::INFINITY, last);
+    let tail_y = sample(y_layers, last, f64::INFINITY, last);
+    events.push(Event4 {
+        start_time: bpm_list.tick(last),
+        end_time: EVENT_END,
+        start: tail_x,
+        end: tail_x,
+        start2: tail_y,
+        end2: tail_y,
+    });
+    events
+}
+
+/// Bake the sum of the layers into official speed events, which hold a
+/// constant value. Changing speed is approximated by steps.
+pub(crate) fn bake_speed_events(layers: &[Vec<Curve>], bpm_list: &BpmList) -> Vec<Event1> {
+    let points = breakpoints(&[layers], bpm_list);
+    let (Some(&first), Some(&last)) = (points.first(), points.last()) else {
+        return vec![Event1 {
+            start_time: 0.0,
+            end_time: EVENT_END,
+            value: sample(layers, 0.0, 0.0, 0.0),
+        }];
+    };
+    let mut events = Vec::new();
+    let first_tick = bpm_list.tick(first);
+    if first_tick > 0.0 {
+        events.push(Event1 {
+            start_time: 0.0,
+            end_time: first_tick,
+            value: sample(layers, f64::NEG_INFINITY, first, first),
+        });
+    }
+    for window in points.windows(2) {
+        let (from, to) = (window[0], window[1]);
+        for (a, b) in steps(is_constant(layers, from, to), from, to) {
+            events.push(Event1 {
+                start_time: bpm_list.tick(a),
+                end_time: bpm_list.tick(b),
+                value: sample(layers, from, to, f64::midpoint(a, b)),
+            });
+        }
+    }
+    events.push(Event1 {
+        start_time: bpm_list.tick(last),
+        end_time: EVENT_END,
+        value: sample(layers, last, f64::INFINITY, last),
+    });
+    events
+}
+
+/// Get the value of baked speed events at `tick_time`
+pub(crate) fn speed_at(speed_events: &[Event1], tick_time: f64) -> f64 {
+    speed_events
+        .iter()
+        .find(|it| it.start_time <= tick_time && tick_time < it.end_time)
+        .or(speed_events.last())
+        .map_or(1.0, |it| it.value)
+}
@@ -0,0 +1,411 @@
+//! The `PhiEdit` (PEC) text chart format.
+
+use crate::{
+    chart::{Chart, ConversionIssue, ConversionReport, JudgeLine, Note, NoteType},
+    chart_events::{self, BpmList, Curve, Easing},
+    error::ChartLoadError,
+    states::get_seconds_per_tick,
+    states_lines,
+};
+
+const PEC_WIDTH: f64 = 2048.0;
+const PEC_HEIGHT: f64 = 1400.0;
+const PEC_NOTE_UNIT: f64 = PEC_WIDTH / 2.0 / 9.0;
+const PEC_SPEED_RATIO: f64 = 1.0 / 5.85;
+const PEC_OFFSET_SHIFT: f64 = 0.15;
+
+#[derive(Clone)]
+pub struct NotePec {
+    pub line: usize,
+    pub r#type: NoteType,
+    pub start_beat: f64,
+    pub end_beat: f64,
+    pub position_x: f64,
+    pub above: bool,
+    pub is_fake: bool,
+    pub speed: f64,
+    pub size: f64,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Channel {
+    Speed,
+    Move,
+    Rotate,
+    Alpha,
+}
+
+#[derive(Clone)]
+pub struct EventPec {
+    pub line: usize,
+    pub channel: Channel,
+    pub start_beat: f64,
+    pub end_beat: f64,
+    pub value: (f64, f64),
+    pub easing: i32,
+}
+
+#[derive(Clone)]
+pub struct ChartPec {
+    pub offset: f64,
+    pub bpm_list: Vec<(f64, f64)>,
+    pub notes: Vec<NotePec>,
+    pub events: Vec<EventPec>,
+}
+
+struct Tokens<'a> {
+    iter: Box<dyn Iterator<Item = (usize, &'a str)> + 'a>,
+    line: usize,
+}
+
+impl<'a> Tokens<'a> {
+    fn new(text: &'a str) -> Tokens<'a> {
+        Tokens {
+            iter: Box::new(
+                text.lines()
+                    .enumerate()
+                    .flat_map(|(i, line)| line.split_whitespace().map(move |it| (i + 1, it))),
+            ),
+            line: 1,
+        }
+    }
+
+    fn next(&mut self) -> Option<&'a str> {
+        self.iter.next().map(|(line, token)| {
+            self.line = line;
+            token
+        })
+    }
+
+    fn error(&self, message: String) -> ChartLoadError {
+        ChartLoadError::InvalidPec {
+            line: self.line,
+            message,
+        }
+    }
+
+    fn number(&mut self, command: &str) -> Result<f64, ChartLoadError> {
+        let token = self
+            .next()
+            .ok_or_else(|| self.error(format!("unexpected end of file in `{command}`")))?;
+        token
+            .parse::<f64>()
+            .ok()
+            .filter(|it| it.is_finite())
+            .ok_or_else(|| self.error(format!("invalid number `{token}` in `{command}`")))
+    }
+
+    #[allow(clippy::cast_sign_loss)]
+    fn index(&mut self, command: &str) -> Result<usize, ChartLoadError> {
+        let value = self.number(command)?;
+        if value < 0.0 || value.fract() != 0.0 || value > f64::from(u16::MAX) {
+            return Err(self.error(format!("invalid line index `{value}` in `{command}`")));
+        }
+        Ok(value as usize)
+    }
+
+    fn integer(&mut self, command: &str) -> Result<i32, ChartLoadError> {
+        let value = self.number(command)?;
+        if value.fract() != 0.0 || value.abs() > f64::from(i32::MAX) {
+            return Err(self.error(format!("invalid integer `{value}` in `{command}`")));
+        }
+        Ok(value as i32)
+    }
+}
+
+impl Tokens<'_> {
+    fn note(&mut self, command: &str) -> Result<NotePec, ChartLoadError> {
+        let line = self.index(command)?;
+        let start_beat = self.number(command)?;
+        let end_beat = if command == "n2" {
+            self.number(command)?
+        } else {
+            start_beat
+        };
+        let position_x = self.number(command)?;
+        let above = self.integer(command)? == 1;
+        let is_fake = self.integer(command)? != 0;
+        Ok(NotePec {
+            line,
+            r#type: match command {
+                "n1" => NoteType::Tap,
+                "n2" => NoteType::Hold,
+                "n3" => NoteType::Flick,
+                _ => NoteType::Drag,
+            },
+            start_beat,
+            end_beat,
+            position_x,
+            above,
+            is_fake,
+            speed: 1.0,
+            size: 1.0,
+        })
+    }
+
+    fn event(&mut self, command: &str) -> Result<EventPec, ChartLoadError> {
+        let motion = matches!(command, "cm" | "cr" | "cf");
+        let line = self.index(command)?;
+        let start_beat = self.number(command)?;
+        let end_beat = if motion {
+            self.number(command)?
+        } else {
+            start_beat
+        };
+        let first = self.number(command)?;
+        let second = if command == "cp" || command == "cm" {
+            self.number(command)?
+        } else {
+            first
+        };
+        let easing = if command == "cm" || command == "cr" {
+            self.integer(command)?
+        } else {
+            1
+        };
+        Ok(EventPec {
+            line,
+            channel: match command {
+                "cv" => Channel::Speed,
+                "cp" | "cm" => Channel::Move,
+                "cd" | "cr" => Channel::Rotate,
+                _ => Channel::Alpha,
+            },
+            start_beat,
+            end_beat,
+            value: (first, second),
+            easing,
+        })
+    }
+}
+
+impl ChartPec {
+    /// Parse the text of a PEC chart
+    pub fn parse(text: &str) -> Result<ChartPec, ChartLoadError> {
+        let mut tokens = Tokens::new(text);
+        let offset = tokens.number("offset")? / 1000.0 - PEC_OFFSET_SHIFT;
+        let mut chart = ChartPec {
+            offset,
+            bpm_list: Vec::new(),
+            notes: Vec::new(),
+            events: Vec::new(),
+        };
+        while let Some(command) = tokens.next() {
+            match command {
+                "bp" => {
+                    let beat = tokens.number(command)?;
+                    let bpm = tokens.number(command)?;
+                    if bpm <= 0.0 {
+                        return Err(tokens.error(format!("non-positive bpm: {bpm}")));
+                    }
+                    chart.bpm_list.push((beat, bpm));
+                }
+                "n1" | "n2" | "n3" | "n4" => chart.notes.push(tokens.note(command)?),
+                "#" | "&" => {
+                    let value = tokens.number(command)?;
+                    let note = chart
+                        .notes
+                        .last_mut()
+                        .ok_or_else(|| tokens.error(format!("`{command}` without a note")))?;
+                    if command == "#" {
+                        note.speed = value;
+                    } else {
+                        note.size = value;
+                    }
+                }
+                "cv" | "cp" | "cd" | "ca" | "cm" | "cr" | "cf" => {
+                    chart.events.push(tokens.event(command)?);
+                }
+                _ => return Err(tokens.error(format!("unknown command `{command}`"))),
+            }
+        }
+        if chart.bpm_list.is_empty() {
+            return Err(tokens.error("missing `bp` command".to_string()));
+        }
+        Ok(chart)
+    }
+
+    /// Convert the chart to the official v3 format, reporting everything that
+    /// can not be expressed by it
+    pub fn convert_to_v3(self) -> (Chart, ConversionReport) {
+        let mut report = ConversionReport::default();
+        let bpm_list = BpmList::new(self.bpm_list);
+        let line_count = self
+            .notes
+            .iter()
+            .map(|it| it.line)
+            .chain(self.events.iter().map(|it| it.line))
+            .max()
+            .map_or(0, |it| it + 1);
+        let mut events = vec![Vec::new(); line_count];
+        for event in self.events {
+            events[event.line].push(event);
+        }
+        let judge_line_list = events
+            .into_iter()
+            .enumerate()
+            .map(|(i, events)| {
+                let mut line = convert_events(i, &events, &bpm_list, &mut report);
+                convert_notes(i, &self.notes, &bpm_list, &mut line, &mut report);
+                line
+            })
+            .collect();
+        (
+            Chart {
+                offset: self.offset,
+                judge_line_list,
+            },
+            report,
+        )
+    }
+}
+
+/// Build the curves of a channel. A PEC motion only states its target, so it
+/// starts from the value the channel has when the motion begins.
+fn to_curves(
+    events: &[EventPec],
+    channel: Channel,
+    pick: fn((f64, f64)) -> f64,
+    issue: &mut impl FnMut(String),
+) -> Vec<Curve> {
+    let mut events = events
+        .iter()
+        .filter(|it| it.channel == channel)
+        .collect::<Vec<_>>();
+    events.sort_by(|a, b| a.start_beat.total_cmp(&b.start_beat));
+    let mut curves = Vec::<Curve>::with_capacity(events.len());
+    for event in events {
+        if !Easing::is_supported(event.easing) {
+            issue(format!(
+                "easing type {} is not supported, linear easing is used instead",
+                event.easing
+            ));
+        }
+        let target = pick(event.value);
+        let start = curves
+            .last()
+            .map_or(target, |it| it.value_at(event.start_beat));
+        let start = if event.start_beat < event.end_beat {
+            start
+        } else {
+            target
+        };
+        curves.push(Curve {
+            start_beat: event.start_beat,
+            end_beat: event.end_beat.max(event.start_beat),
+            start,
+            end: target,
+            easing: Easing {
+                kind: event.easing,
+                left: 0.0,
+                right: 1.0,
+            },
+        });
+    }
+    curves
+}
+
+fn convert_events(
+    index: usize,
+    events: &[EventPec],
+    bpm_list: &BpmList,
+    report: &mut ConversionReport,
+) -> JudgeLine {
+    let mut issue = |message: String| {
+        report.issues.push(ConversionIssue {
+            line: Some(index),
+            note: None,
+            message,
+        });
+    };
+    let move_x = to_curves(events, Channel::Move, |it| it.0, &mut issue);
+    let move_y = to_curves(events, Channel::Move, |it| it.1, &mut issue);
+    let rotate = to_curves(events, Channel::Rotate, |it| it.0, &mut issue);
+    let alpha = to_curves(events, Channel::Alpha, |it| it.0, &mut issue);
+    let speed = to_curves(events, Channel::Speed, |it| it.0, &mut issue);
+    let mut move_events = chart_events::bake_move_events(&[move_x], &[move_y], bpm_list);
+    for event in &mut move_events {
+        event.start /= PEC_WIDTH;
+        event.end /= PEC_WIDTH;
+        event.start2 /= PEC_HEIGHT;
+        event.end2 /= PEC_HEIGHT;
+    }
+    let mut rotate_events = chart_events::bake_events(&[rotate], bpm_list);
+    for event in &mut rotate_events {
+        event.start = -event.start;
+        event.end = -event.end;
+    }
+    let mut alpha_events = chart_events::bake_events(&[alpha], bpm_list);
+    if alpha_events.iter().any(|it| it.start < 0.0 || it.end < 0.0) {
+        issue("negative alpha is not supported and is treated as 0".to_string());
+    }
+    for event in &mut alpha_events {
+        event.start = (event.start / 255.0).clamp(0.0, 1.0);
+        event.end = (event.end / 255.0).clamp(0.0, 1.0);
+    }
+    let mut speed_events = chart_events::bake_speed_events(&[speed], bpm_list);
+    for event in &mut speed_events {
+        event.value *= PEC_SPEED_RATIO;
+    }
+    JudgeLine {
+        bpm: bpm_list.base_bpm(),
+        notes_above: Vec::new(),
+        notes_below: Vec::new(),
+        speed_events,
+        move_events,
+        rotate_events,
+        alpha_events,
+    }
+}
+
+fn convert_notes(
+    index: usize,
+    notes: &[NotePec],
+    bpm_list: &BpmList,
+    line: &mut JudgeLine,
+    report: &mut ConversionReport,
+) {
+    let seconds_per_tick = get_seconds_per_tick(bpm_list.base_bpm());
+    for (j, note) in notes.iter().filter(|it| it.line == index).enumerate() {
+        let mut issue = |message: String| {
+            report.issues.push(ConversionIssue {
+                line: Some(index),
+                note: Some(j),
+                message,
+            });
+        };
+        if note.is_fake {
+            issue("fake note is not supported and is dropped".to_string());
+            continue;
+        }
+        if (note.size - 1.0).abs() > f64::EPSILON {
+            issue("size of the note is ignored".to_string());
+        }
+        let time = bpm_list.tick(note.start_beat).round() as i32;
+        let (hold_time, speed) = if note.r#type == NoteType::Hold {
+            (
+                (bpm_list.tick(note.end_beat) - f64::from(time)).max(0.0),
+                note.speed * chart_events::speed_at(&line.speed_events, f64::from(time)),
+            )
+        } else {
+            (0.0, note.speed)
+        };
+        let converted = Note {
+            r#type: note.r#type,
+            time,
+            position_x: note.position_x / PEC_NOTE_UNIT,
+            hold_time,
+            speed,
+            floor_position: states_lines::get_floor_position(
+                &line.speed_events,
+                seconds_per_tick,
+                f64::from(time),
+            ),
+        };
+        if note.above {
+            line.notes_above.push(converted);
+        } else {
+            line.notes_below.push(converted);
+        }
+    }
+}
@@ -0,0 +1,391 @@
+//! The Re:PhiEdit (RPE) chart format.
+
+use serde::Deserialize;
+
+use crate::{
+    chart::{Chart, ConversionIssue, ConversionReport, Event1, JudgeLine, Note, NoteType},
+    chart_events::{self, BpmList, Curve, Easing},
+    states::get_seconds_per_tick,
+    states_lines,
+};
+
+const RPE_WIDTH: f64 = 1350.0;
+const RPE_HEIGHT: f64 = 900.0;
+const RPE_SPEED_RATIO: f64 = 10.0 / 45.0;
+const RPE_NOTE_UNIT: f64 = RPE_WIDTH / 18.0;
+
+/// A time in beats, written as `[integer, numerator, denominator]`
+#[derive(Deserialize, Clone, Copy)]
+pub struct BeatTime(pub i32, pub i32, pub i32);
+
+impl BeatTime {
+    pub fn beat(self) -> f64 {
+        let BeatTime(integer, numerator, denominator) = self;
+        if denominator == 0 {
+            f64::from(integer)
+        } else {
+            f64::from(integer) + f64::from(numerator) / f64::from(denominator)
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+pub struct ChartRpe {
+    #[serde(rename = "BPMList")]
+    pub bpm_list: Vec<BpmRpe>,
+    #[serde(rename = "META")]
+    pub meta: MetaRpe,
+    #[serde(rename = "judgeLineList")]
+    pub judge_line_list: Vec<JudgeLineRpe>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct BpmRpe {
+    pub bpm: f64,
+    #[serde(rename = "startTime")]
+    pub start_time: BeatTime,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct MetaRpe {
+    #[serde(rename = "RPEVersion", default)]
+    pub rpe_version: i32,
+    #[serde(default)]
+    pub offset: f64,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct JudgeLineRpe {
+    #[serde(rename = "eventLayers", default)]
+    pub event_layers: Vec<Option<EventLayerRpe>>,
+    #[serde(default)]
+    pub notes: Vec<NoteRpe>,
+    #[serde(default = "default_father")]
+    pub father: i32,
+    #[serde(rename = "bpmfactor", default = "default_one")]
+    pub bpm_factor: f64,
+    #[serde(default)]
+    pub extended: Option<serde_json::Value>,
+    #[serde(rename = "Texture", default)]
+    pub texture: Option<String>,
+    #[serde(rename = "attachUI", default)]
+    pub attach_ui: Option<String>,
+}
+
+#[allow(clippy::struct_field_names)]
+#[derive(Deserialize, Clone, Default)]
+pub struct EventLayerRpe {
+    #[serde(rename = "alphaEvents", default)]
+    pub alpha_events: Option<Vec<EventRpe>>,
+    #[serde(rename = "moveXEvents", default)]
+    pub move_x_events: Option<Vec<EventRpe>>,
+    #[serde(rename = "moveYEvents", default)]
+    pub move_y_events: Option<Vec<EventRpe>>,
+    #[serde(rename = "rotateEvents", default)]
+    pub rotate_events: Option<Vec<EventRpe>>,
+    #[serde(rename = "speedEvents", default)]
+    pub speed_events: Option<Vec<EventRpe>>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct EventRpe {
+    #[serde(rename = "startTime")]
+    pub start_time: BeatTime,
+    #[serde(rename = "endTime")]
+    pub end_time: BeatTime,
+    pub start: f64,
+    pub end: f64,
+    #[serde(rename = "easingType", default = "default_easing")]
+    pub easing_type: i32,
+    #[serde(rename = "easingLeft", default)]
+    pub easing_left: f64,
+    #[serde(rename = "easingRight", default = "default_one")]
+    pub easing_right: f64,
+    #[serde(default)]
+    pub bezier: i32,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct NoteRpe {
+    #[serde(rename = "type")]
+    pub r#type: i32,
+    #[serde(rename = "startTime")]
+    pub start_time: BeatTime,
+    #[serde(rename = "endTime")]
+    pub end_time: BeatTime,
+    #[serde(rename = "positionX")]
+    pub position_x: f64,
+    #[serde(default = "default_above")]
+    pub above: i32,
+    #[serde(rename = "isFake", default)]
+    pub is_fake: i32,
+    #[serde(default = "default_alpha")]
+    pub alpha: i32,
+    #[serde(default = "default_one")]
+    pub size: f64,
+    #[serde(default = "default_one")]
+    pub speed: f64,
+    #[serde(rename = "yOffset", default)]
+    pub y_offset: f64,
+    #[serde(rename = "visibleTime", default = "default_visible_time")]
+    pub visible_time: f64,
+}
+
+fn default_father() -> i32 {
+    -1
+}
+
+fn default_one() -> f64 {
+    1.0
+}
+
+fn default_easing() -> i32 {
+    1
+}
+
+fn default_above() -> i32 {
+    1
+}
+
+fn default_alpha() -> i32 {
+    255
+}
+
+fn default_visible_time() -> f64 {
+    999_999.0
+}
+
+impl NoteRpe {
+    fn note_type(&self) -> Option<NoteType> {
+        match self.r#type {
+            1 => Some(NoteType::Tap),
+            2 => Some(NoteType::Hold),
+            3 => Some(NoteType::Flick),
+            4 => Some(NoteType::Drag),
+            _ => None,
+        }
+    }
+}
+
+impl ChartRpe {
+    pub(crate) fn bpm_list(&self) -> BpmList {
+        BpmList::new(
+            self.bpm_list
+                .iter()
+                .map(|it| (it.start_time.beat(), it.bpm))
+                .collect(),
+        )
+    }
+
+    /// Convert the chart to the official v3 format, reporting everything that
+    /// can not be expressed by it
+    pub fn convert_to_v3(self) -> (Chart, ConversionReport) {
+        let mut report = ConversionReport::default();
+        let bpm_list = self.bpm_list();
+        let judge_line_list = self
+            .judge_line_list
+            .into_iter()
+            .enumerate()
+            .map(|(i, line)| convert_line(i, line, &bpm_list, &mut report))
+            .collect();
+        (
+            Chart {
+                offset: self.meta.offset / 1000.0,
+                judge_line_list,
+            },
+            report,
+        )
+    }
+}
+
+fn convert_line(
+    index: usize,
+    line: JudgeLineRpe,
+    bpm_list: &BpmList,
+    report: &mut ConversionReport,
+) -> JudgeLine {
+    let mut issue = |message: String| {
+        report.issues.push(ConversionIssue {
+            line: Some(index),
+            note: None,
+            message,
+        });
+    };
+    if line.father != -1 {
+        issue(format!(
+            "parent line {} is not supported, the line moves on its own",
+            line.father
+        ));
+    }
+    if (line.bpm_factor - 1.0).abs() > f64::EPSILON {
+        issue(format!(
+            "bpm factor {} is not supported and is ignored",
+            line.bpm_factor
+        ));
+    }
+    if line
+        .texture
+        .as_deref()
+        .is_some_and(|it| it != "line.png" && !it.is_empty())
+    {
+        issue("custom line texture is not supported".to_string());
+    }
+    if line.attach_ui.as_deref().is_some_and(|it| !it.is_empty()) {
+        issue("attaching the line to UI is not supported".to_string());
+    }
+    if has_extended_events(line.extended.as_ref()) {
+        issue("extended events (scale, color, text, incline) are not supported".to_string());
+    }
+    let layers = line.event_layers.into_iter().flatten().collect::<Vec<_>>();
+    let mut curves = |pick: fn(&EventLayerRpe) -> Option<&Vec<EventRpe>>| -> Vec<Vec<Curve>> {
+        layers
+            .iter()
+            .map(|layer| {
+                pick(layer)
+                    .map(|events| events.iter().map(|it| to_curve(it, &mut issue)).collect())
+                    .unwrap_or_default()
+            })
+            .collect()
+    };
+    let move_x = curves(|it| it.move_x_events.as_ref());
+    let move_y = curves(|it| it.move_y_events.as_ref());
+    let rotate = curves(|it| it.rotate_events.as_ref());
+    let alpha = curves(|it| it.alpha_events.as_ref());
+    let speed = curves(|it| it.speed_events.as_ref());
+    let mut move_events = chart_events::bake_move_events(&move_x, &move_y, bpm_list);
+    for event in &mut move_events {
+        event.start = (event.start + RPE_WIDTH / 2.0) / RPE_WIDTH;
+        event.end = (event.end + RPE_WIDTH / 2.0) / RPE_WIDTH;
+        event.start2 = (event.start2 + RPE_HEIGHT / 2.0) / RPE_HEIGHT;
+        event.end2 = (event.end2 + RPE_HEIGHT / 2.0) / RPE_HEIGHT;
+    }
+    let mut rotate_events = chart_events::bake_events(&rotate, bpm_list);
+    for event in &mut rotate_events {
+        event.start = -event.start;
+        event.end = -event.end;
+    }
+    let mut alpha_events = chart_events::bake_events(&alpha, bpm_list);
+    if alpha_events.iter().any(|it| it.start < 0.0 || it.end < 0.0) {
+        issue("negative alpha is not supported and is treated as 0".to_string());
+    }
+    for event in &mut alpha_events {
+        event.start = (event.start / 255.0).clamp(0.0, 1.0);
+        event.end = (event.end / 255.0).clamp(0.0, 1.0);
+    }
+    let mut speed_events = chart_events::bake_speed_events(&speed, bpm_list);
+    for event in &mut speed_events {
+        event.value *= RPE_SPEED_RATIO;
+    }
+    let bpm = bpm_list.base_bpm();
+    let mut notes_above = Vec::new();
+    let mut notes_below = Vec::new();
+    for (j, note) in line.notes.iter().enumerate() {
+        if let Some(converted) = convert_note(note, bpm_list, &speed_events, |message| {
+            report.issues.push(ConversionIssue {
+                line: Some(index),
+                note: Some(j),
+                message,
+            });
+        }) {
+            if note.above == 1 {
+                notes_above.push(converted);
+            } else {
+                notes_below.push(converted);
+            }
+        }
+    }
+    JudgeLine {
+        bpm,
+        notes_above,
+        notes_below,
+        speed_events,
+        move_events,
+        rotate_events,
+        alpha_events,
+    }
+}
+
+fn convert_note(
+    note: &NoteRpe,
+    bpm_list: &BpmList,
+    speed_events: &[Event1],
+    mut issue: impl FnMut(String),
+) -> Option<Note> {
+    let Some(note_type) = note.note_type() else {
+        issue(format!("unknown note type {} is dropped", note.r#type));
+        return None;
+    };
+    if note.is_fake != 0 {
+        issue("fake note is not supported and is dropped".to_string());
+        return None;
+    }
+    let mut ignored = Vec::new();
+    if note.alpha != 255 {
+        ignored.push("alpha");
+    }
+    if (note.size - 1.0).abs() > f64::EPSILON {
+        ignored.push("size");
+    }
+    if note.y_offset.abs() > f64::EPSILON {
+        ignored.push("yOffset");
+    }
+    if note.visible_time < default_visible_time() {
+        ignored.push("visibleTime");
+    }
+    if !ignored.is_empty() {
+        issue(format!("{} of the note is ignored", ignored.join(", ")));
+    }
+    let time = bpm_list.tick(note.start_time.beat()).round() as i32;
+    let (hold_time, speed) = if note_type == NoteType::Hold {
+        (
+            (bpm_list.tick(note.end_time.beat()) - f64::from(time)).max(0.0),
+            note.speed * chart_events::speed_at(speed_events, f64::from(time)),
+        )
+    } else {
+        (0.0, note.speed)
+    };
+    Some(Note {
+        r#type: note_type,
+        time,
+        position_x: note.position_x / RPE_NOTE_UNIT,
+        hold_time,
+        speed,
+        floor_position: states_lines::get_floor_position(
+            speed_events,
+            get_seconds_per_tick(bpm_list.base_bpm()),
+            f64::from(time),
+        ),
+    })
+}
+
+fn to_curve(event: &EventRpe, issue: &mut impl FnMut(String)) -> Curve {
+    if event.bezier != 0 {
+        issue("bezier easing is not supported, the easing type is used instead".to_string());
+    }
+    if !Easing::is_supported(event.easing_type) {
+        issue(format!(
+            "easing type {} is not supported, linear easing is used instead",
+            event.easing_type
+        ));
+    }
+    Curve {
+        start_beat: event.start_time.beat(),
+        end_beat: event.end_time.beat(),
+        start: event.start,
+        end: event.end,
+        easing: Easing {
+            kind: event.easing_type,
+            left: event.easing_left,
+            right: event.easing_right,
+        },
+    }
+}
+
+fn has_extended_events(extended: Option<&serde_json::Value>) -> bool {
+    extended
+        .and_then(serde_json::Value::as_object)
+        .is_some_and(|it| {
+            it.values()
+                .any(|events| events.as_array().is_some_and(|it| !it.is_empty()))
+        })
+}
@@ -0,0 +1,408 @@
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::{
+    chart::{Chart, Event1, Event2, Event4, JudgeLine, Note, NoteType, WithTimeRange},
+    states::get_seconds_per_tick,
+    states_lines,
+};
+
+const TIME_EPSILON: f64 = 1e-3;
+const FLOOR_POSITION_TOLERANCE: f64 = 0.01;
+const MAX_POSITION_X: f64 = 9.0;
+
+/// How serious a diagnostic is
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Severity {
+    /// The chart can be played, but probably not as intended
+    Warning,
+
+    /// The engine would misbehave or panic on the chart
+    Error,
+}
+
+/// The part of the chart a diagnostic belongs to
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DiagnosticTarget {
+    /// The whole chart
+    Chart,
+
+    /// The judge line itself
+    Line,
+
+    /// The note at the index of `notesAbove`
+    NoteAbove(usize),
+
+    /// The note at the index of `notesBelow`
+    NoteBelow(usize),
+
+    /// The event at the index of `speedEvents`
+    SpeedEvent(usize),
+
+    /// The event at the index of `judgeLineMoveEvents`
+    MoveEvent(usize),
+
+    /// The event at the index of `judgeLineRotateEvents`
+    RotateEvent(usize),
+
+    /// The event at the index of `judgeLineDisappearEvents`
+    AlphaEvent(usize),
+}
+
+/// A problem found in a chart
+#[derive(Serialize, Clone, Debug)]
+pub struct Diagnostic {
+    /// How serious the problem is
+    pub severity: Severity,
+
+    /// The index of the judge line, if the problem belongs to a line
+    pub line: Option<usize>,
+
+    /// The part of the chart or line the problem belongs to
+    pub target: DiagnosticTarget,
+
+    /// What is wrong
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        write!(f, "{severity}")?;
+        if let Some(line) = self.line {
+            write!(f, " at line {line}")?;
+        }
+        match self.target {
+            DiagnosticTarget::Chart | DiagnosticTarget::Line => {}
+            DiagnosticTarget::NoteAbove(i) => write!(f, ", notesAbove[{i}]")?,
+            DiagnosticTarget::NoteBelow(i) => write!(f, ", notesBelow[{i}]")?,
+            DiagnosticTarget::SpeedEvent(i) => write!(f, ", speedEvents[{i}]")?,
+            DiagnosticTarget::MoveEvent(i) => write!(f, ", judgeLineMoveEvents[{i}]")?,
+            DiagnosticTarget::RotateEvent(i) => write!(f, ", judgeLineRotateEvents[{i}]")?,
+            DiagnosticTarget::AlphaEvent(i) => write!(f, ", judgeLineDisappearEvents[{i}]")?,
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+impl Diagnostic {
+    pub(crate) fn chart_error(message: String) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Error,
+            line: None,
+            target: DiagnosticTarget::Chart,
+            message,
+        }
+    }
+}
+
+/// Check a chart for problems that make the engine misbehave.
+///
+/// Only values the engine can not compute with, like a non-finite number or a
+/// non-positive bpm, are a `Severity::Error`, which the json and pec loaders
+/// reject. Everything else is a warning and the chart is loaded as before.
+///
+/// Note types are not checked, a `Chart` can only hold valid ones.
+#[must_use]
+pub fn validate(chart: &Chart) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    if !chart.offset.is_finite() {
+        diagnostics.push(Diagnostic::chart_error(format!(
+            "offset is not finite: {}",
+            chart.offset
+        )));
+    }
+    for (i, line) in chart.judge_line_list.iter().enumerate() {
+        validate_line(i, line, &mut diagnostics);
+    }
+    diagnostics
+}
+
+fn validate_line(index: usize, line: &JudgeLine, diagnostics: &mut Vec<Diagnostic>) {
+    let mut push = |severity: Severity, target: DiagnosticTarget, message: String| {
+        diagnostics.push(Diagnostic {
+            severity,
+            line: Some(index),
+            target,
+            message,
+        });
+    };
+    let bpm_valid = line.bpm.is_finite() && line.bpm > 0.0;
+    if !bpm_valid {
+        push(
+            Severity::Error,
+            DiagnosticTarget::Line,
+            format!("bpm must be positive: {}", line.bpm),
+        );
+    }
+    let speed_valid = check_events(
+        &line.speed_events,
+        |it: &Event1| [it.value].iter().all(|it| it.is_finite()),
+        DiagnosticTarget::SpeedEvent,
+        &mut push,
+    );
+    check_events(
+        &line.move_events,
+        |it: &Event4| {
+            [it.start, it.end, it.start2, it.end2]
+                .iter()
+                .all(|it| it.is_finite())
+        },
+        DiagnosticTarget::MoveEvent,
+        &mut push,
+    );
+    check_events(
+        &line.rotate_events,
+        |it: &Event2| [it.start, it.end].iter().all(|it| it.is_finite()),
+        DiagnosticTarget::RotateEvent,
+        &mut push,
+    );
+    check_events(
+        &line.alpha_events,
+        |it: &Event2| [it.start, it.end].iter().all(|it| it.is_finite()),
+        DiagnosticTarget::AlphaEvent,
+        &mut push,
+    );
+    let expected_floor = |note: &Note| {
+        (bpm_valid && speed_valid).then(|| {
+            states_lines::get_floor_position(
+                &line.speed_events,
+                get_seconds_per_tick(line.bpm),
+                f64::from(note.time),
+            )
+        })
+    };
+    for (j, note) in line.notes_above.iter().enumerate() {
+        check_note(
+            note,
+            expected_floor(note),
+            DiagnosticTarget::NoteAbove(j),
+            &mut push,
+        );
+    }
+    for (j, note) in line.notes_below.iter().enumerate() {
+        check_note(
+            note,
+            expected_floor(note),
+            DiagnosticTarget::NoteBelow(j),
+            &mut push,
+        );
+    }
+}
+
+/// Check the events of one kind, returns whether they are usable for
+/// integrating the floor position. Unsorted or reversed events still play,
+/// but the floor position integrated from them is meaningless.
+fn check_events<T: WithTimeRange>(
+    events: &[T],
+    is_finite: impl Fn(&T) -> bool,
+    target: fn(usize) -> DiagnosticTarget,
+    push: &mut impl FnMut(Severity, DiagnosticTarget, String),
+) -> bool {
+    let mut valid = true;
+    let mut last: Option<&T> = None;
+    for (i, event) in events.iter().enumerate() {
+        if !is_finite(event) || !event.time_start().is_finite() || !event.time_end().is_finite() {
+            push(
+                Severity::Error,
+                target(i),
+                "event contains a value that is not finite".to_string(),
+            );
+            valid = false;
+            continue;
+        }
+        if event.time_end() < event.time_start() {
+            push(
+                Severity::Warning,
+                target(i),
+                format!(
+                    "event ends before it starts: {} < {}",
+                    event.time_end(),
+                    event.time_start()
+                ),
+            );
+            valid = false;
+        }
+        if let Some(last) = last {
+            if event.time_start() < last.time_start() {
+                push(
+                    Severity::Warning,
+                    target(i),
+                    "event starts before the previous event, events must be sorted".to_string(),
+                );
+                valid = false;
+            } else if event.time_start() < last.time_end() - TIME_EPSILON {
+                push(
+                    Severity::Warning,
+                    target(i),
+                    format!(
+                        "event overlaps the previous event, which ends at {}",
+                        last.time_end()
+                    ),
+                );
+            } else if event.time_start() > last.time_end() + TIME_EPSILON {
+                push(
+                    Severity::Warning,
+                    target(i),
+                    format!(
+                        "gap between {} and {} is not covered by any event",
+                        last.time_end(),
+                        event.time_start()
+                    ),
+                );
+            }
+        }
+        last = Some(event);
+    }
+    valid
+}
+
+fn check_note(
+    note: &Note,
+    expected_floor: Option<f64>,
+    target: DiagnosticTarget,
+    push: &mut impl FnMut(Severity, DiagnosticTarget, String),
+) {
+    if ![
+        note.position_x,
+        note.hold_time,
+        note.speed,
+        note.floor_position,
+    ]
+    .iter()
+    .all(|it| it.is_finite())
+    {
+        push(
+            Severity::Error,
+            target,
+            "note contains a value that is not finite".to_string(),
+        );
+        return;
+    }
+    if note.time < 0 {
+        push(
+            Severity::Warning,
+            target,
+            format!("note time is negative: {}", note.time),
+        );
+    }
+    match note.r#type {
+        NoteType::Hold if note.hold_time <= 0.0 => push(
+            Severity::Warning,
+            target,
+            format!("hold note has a non-positive hold time: {}", note.hold_time),
+        ),
+        NoteType::Tap | NoteType::Drag | NoteType::Flick if note.hold_time > 0.0 => push(
+            Severity::Warning,
+            target,
+            format!(
+                "non-hold note has a hold time of {}, the type may be wrong",
+                note.hold_time
+            ),
+        ),
+        _ => {}
+    }
+    if note.position_x.abs() > MAX_POSITION_X {
+        push(
+            Severity::Warning,
+            target,
+            format!("note is off the screen at x {}", note.position_x),
+        );
+    }
+    if let Some(expected) = expected_floor
+        && (note.floor_position - expected).abs() > FLOOR_POSITION_TOLERANCE
+    {
+        push(
+            Severity::Warning,
+            target,
+            format!(
+                "floor position {} does not match {} computed from the speed events",
+                note.floor_position, expected
+            ),
+        );
+    }
+}
+
+/// How far the floor positions stored in a chart were off from the ones
+/// computed from the speed events
+#[derive(Serialize, Clone, Default, Debug)]
+pub struct FloorPositionReport {
+    /// The number of notes whose floor position was recomputed
+    pub recomputed: usize,
+
+    /// The notes whose stored floor position was off by more than the
+    /// tolerance
+    pub changes: Vec<FloorPositionChange>,
+
+    /// The largest difference between a stored and a computed floor position
+    pub max_delta: f64,
+}
+
+/// A note whose floor position was replaced
+#[derive(Serialize, Clone, Debug)]
+pub struct FloorPositionChange {
+    /// The index of the judge line
+    pub line: usize,
+
+    /// The note, either `NoteAbove` or `NoteBelow`
+    pub target: DiagnosticTarget,
+
+    /// The floor position stored in the chart
+    pub stored: f64,
+
+    /// The floor position computed from the speed events
+    pub computed: f64,
+}
+
+/// Replace the floor position of every note with the one computed from the
+/// speed events of its line. Lines whose bpm or speed events are unusable are
+/// left untouched.
+pub(crate) fn recompute_floor_positions(chart: &mut Chart) -> FloorPositionReport {
+    let mut report = FloorPositionReport::default();
+    for (i, line) in chart.judge_line_list.iter_mut().enumerate() {
+        let usable = line.bpm.is_finite()
+            && line.bpm > 0.0
+            && line.speed_events.iter().all(|it| {
+                it.value.is_finite() && it.time_start().is_finite() && it.time_end().is_finite()
+            });
+        if !usable {
+            continue;
+        }
+        let seconds_per_tick = get_seconds_per_tick(line.bpm);
+        let sides = [
+            (
+                &mut line.notes_above,
+                DiagnosticTarget::NoteAbove as fn(usize) -> DiagnosticTarget,
+            ),
+            (&mut line.notes_below, DiagnosticTarget::NoteBelow),
+        ];
+        for (notes, target) in sides {
+            for (j, note) in notes.iter_mut().enumerate() {
+                let computed = states_lines::get_floor_position(
+                    &line.speed_events,
+                    seconds_per_tick,
+                    f64::from(note.time),
+                );
+                let delta = (note.floor_position - computed).abs();
+                if !delta.is_finite() || delta > FLOOR_POSITION_TOLERANCE {
+                    report.changes.push(FloorPositionChange {
+                        line: i,
+                        target: target(j),
+                        stored: note.floor_position,
+                        computed,
+                    });
+                }
+                if delta.is_finite() {
+                    report.max_delta = report.max_delta.max(delta);
+                }
+                note.floor_position = computed;
+                report.recomputed += 1;
+            }
+        }
+    }
+    report
+}
@@ -1,14 +1,12 @@
 use crate::chart::{Note, NoteType};
 use crate::math::{self, Point};
 use crate::renders::{
-    self, Dense, RendClickEffect, RendNote, RendPoint, RendSound, RendSplashEffect, RendStatistics,
+    Dense, EffectDraw, Frame, LineDraw, NoteDraw, PointDraw, RENDER_MAGIC, RENDER_PROTOCOL_VERSION,
+    RendClickEffect, RendFrameHeader, RendLine, RendNote, RendPoint, RendSound, RendSplashEffect,
+    RendStatistics, SoundDraw,
 };
 use crate::states::{LineState, NoteScore, NoteState};
-use crate::states_effect::{HitEffect, SoundEffect, SplashEffect};
-use crate::{
-    CHART_STATISTICS, DRAW_IMAGE_OFFSET, HIT_EFFECT_POOL, LINE_STATES, SOUND_POOL,
-    SPLASH_EFFECT_POOL, TOUCH_STATES,
-};
+use crate::{ENGINE, engine::Engine};
 
 #[allow(clippy::struct_field_names)]
 pub struct DrawImageOffset {
@@ -38,6 +36,89 @@ impl Default for DrawImageOffset {
     }
 }
 
+impl Engine {
+    /// Preloads image section heights.
+    ///
+    /// Takes heights for hold head and hold end sections in both normal and highlighted states.
+    pub fn load_image_offset(
+        &mut self,
+        hold_head_height: f64,
+        hold_head_highlight_height: f64,
+        hold_end_height: f64,
+        hold_end_highlight_height: f64,
+    ) {
+        self.draw_image_offset = DrawImageOffset {
+            hold_head_height,
+            hold_head_highlight_height,
+            hold_end_height,
+            hold_end_highlight_height,
+        };
+    }
+
+    /// Set the colour of a judge line, replacing the tint of the combo
+    /// status. `None` restores the tint. The colour is kept until another
+    /// chart is loaded.
+    pub fn set_line_color(&mut self, line: usize, color: Option<[u8; 3]>) {
+        if let Some(state) = self.line_states.get_mut(line) {
+            state.color = color;
+        }
+    }
+
+    /// Render the internal state to a structured frame.
+    #[must_use]
+    pub fn render_frame(&self) -> Frame {
+        let (notes, hold_notes) = self
+            .line_states
+            .iter()
+            .fold((Vec::new(), Vec::new()), |(v1, v2), it| {
+                process_notes(it, &self.draw_image_offset, v1, v2)
+            });
+        let tint = self.chart_statistics.combo_status.line_color();
+        Frame {
+            frame_time: self.frame_time,
+            statistics: self.chart_statistics.clone(),
+            lines: self
+                .line_states
+                .iter()
+                .filter_map(|it| process_line(it, tint))
+                .collect(),
+            notes: hold_notes.into_iter().chain(notes).collect(),
+            click_effects: self
+                .hit_effect_pool
+                .iter()
+                .filter(|it| it.enable)
+                .map(|it| effect_draw(it.x, it.y, it.progress, it.tint_type))
+                .collect(),
+            splash_effects: self
+                .splash_effect_pool
+                .iter()
+                .filter(|it| it.enable)
+                .map(|it| effect_draw(it.x, it.y, it.progress, it.tint_type))
+                .collect(),
+            sound: SoundDraw {
+                tap_sound: self.sound_pool.tap_count,
+                drag_sound: self.sound_pool.drag_count,
+                flick_sound: self.sound_pool.flick_count,
+            },
+            touches: self
+                .touch_states
+                .iter()
+                .filter(|it| it.enable)
+                .map(|it| PointDraw { x: it.x, y: it.y })
+                .collect(),
+        }
+    }
+
+    /// Render and writes the internal state to the buffer.
+    ///
+    /// The state is written by calling `write` on the provided `BufferWithCursor`.
+    /// The frame starts with a header carrying `RENDER_MAGIC` and
+    /// `RENDER_PROTOCOL_VERSION`, see `RENDER_SCHEMA` for the layout.
+    pub fn process_state_to_drawable(&self, wrapped_buffer: &mut impl BufferWithCursor) {
+        write_frame(wrapped_buffer, &self.render_frame());
+    }
+}
+
 /// Preloads image section heights.
 ///
 /// Takes heights for hold head and hold end sections in both normal and highlighted states.
@@ -47,13 +128,13 @@ pub fn load_image_offset(
     hold_end_height: f64,
     hold_end_highlight_height: f64,
 ) {
-    DRAW_IMAGE_OFFSET.with_borrow_mut(|offset| {
-        *offset = DrawImageOffset {
+    ENGINE.with_borrow_mut(|it| {
+        it.load_image_offset(
             hold_head_height,
             hold_head_highlight_height,
             hold_end_height,
             hold_end_highlight_height,
-        };
+        );
     });
 }
 
@@ -61,100 +142,144 @@ pub fn load_image_offset(
 ///
 /// The state is written by calling `write` on the provided `BufferWithCursor`.
 pub fn process_state_to_drawable(wrapped_buffer: &mut impl BufferWithCursor) {
-    CHART_STATISTICS.with_borrow(|statistics| {
+    ENGINE.with_borrow(|it| it.process_state_to_drawable(wrapped_buffer));
+}
+
+/// Set the colour of a judge line, see `Engine::set_line_color`
+pub fn set_line_color(line: usize, color: Option<[u8; 3]>) {
+    ENGINE.with_borrow_mut(|it| it.set_line_color(line, color));
+}
+
+/// Render the internal state to a structured frame.
+#[must_use]
+pub fn render_frame() -> Frame {
+    ENGINE.with_borrow(Engine::render_frame)
+}
+
+fn write_frame(wrapped_buffer: &mut impl BufferWithCursor, frame: &Frame) {
+    write_frame_header(wrapped_buffer, frame);
+    let statistics = &frame.statistics;
+    wrapped_buffer.write(
+        RendStatistics {
+            rend_type: 5,
+            combo: statistics.combo,
+            max_combo: statistics.max_combo,
+            score: statistics.score as f32,
+            accurate: statistics.accurate as f32,
+            rank: statistics.rank as u8,
+            combo_status: statistics.combo_status as u8,
+            failed: u8::from(statistics.failed_at.is_some()),
+            failed_at: statistics.failed_at.unwrap_or(0.0) as f32,
+        }
+        .to_bytes(),
+    );
+    for it in &frame.lines {
         wrapped_buffer.write(
-            RendStatistics {
-                rend_type: 5,
-                combo: statistics.combo,
-                max_combo: statistics.max_combo,
-                score: statistics.score as f32,
-                accurate: statistics.accurate as f32,
+            RendLine {
+                rend_type: 1,
+                x1: it.x1,
+                y1: it.y1,
+                x2: it.x2,
+                y2: it.y2,
+                alpha: it.alpha,
+                color: it.color,
             }
             .to_bytes(),
         );
-    });
-    LINE_STATES.with_borrow(|states| {
-        DRAW_IMAGE_OFFSET.with_borrow(|offset| {
-            for it in states {
-                write_line(wrapped_buffer, it);
+    }
+    for it in &frame.notes {
+        wrapped_buffer.write(
+            RendNote {
+                rend_type: 2,
+                note_type: it.note_type,
+                x: it.x,
+                y: it.y,
+                rotate: it.rotate,
+                height: it.height,
+                high_light: i8::from(it.high_light),
             }
-            write_notes(wrapped_buffer, states.as_ref(), offset);
-        });
-    });
-    HIT_EFFECT_POOL.with_borrow(|effects| {
-        write_click_effects(wrapped_buffer, effects);
-    });
-    SPLASH_EFFECT_POOL.with_borrow(|effects| {
-        write_splash_effects(wrapped_buffer, effects);
-    });
-    SOUND_POOL.with_borrow(|effects| write_sound_effects(wrapped_buffer, effects));
-    TOUCH_STATES.with_borrow(|touches| {
-        for it in touches {
-            if !it.enable {
-                continue;
+            .to_bytes(),
+        );
+    }
+    for it in &frame.click_effects {
+        wrapped_buffer.write(
+            RendClickEffect {
+                rend_type: 3,
+                x: it.x,
+                y: it.y,
+                frame: it.frame,
+                tint_type: it.tint_type,
             }
-            wrapped_buffer.write(
-                RendPoint {
-                    rend_type: 4,
-                    x: it.x,
-                    y: it.y,
-                }
-                .to_bytes(),
-            );
-        }
-    });
-    wrapped_buffer.write(&[0]);
-}
-
-fn write_sound_effects(wrapped_buffer: &mut impl BufferWithCursor, states: &SoundEffect) {
+            .to_bytes(),
+        );
+    }
+    for it in &frame.splash_effects {
+        wrapped_buffer.write(
+            RendSplashEffect {
+                rend_type: 6,
+                x: it.x,
+                y: it.y,
+                frame: it.frame,
+                tint_type: it.tint_type,
+            }
+            .to_bytes(),
+        );
+    }
     wrapped_buffer.write(
         RendSound {
             rend_type: 7,
-            tap_sound: states.tap_count,
-            drag_sound: states.drag_count,
-            flick_sound: states.flick_count,
+            tap_sound: frame.sound.tap_sound,
+            drag_sound: frame.sound.drag_sound,
+            flick_sound: frame.sound.flick_sound,
         }
         .to_bytes(),
     );
-}
-
-fn write_splash_effects(wrapped_buffer: &mut impl BufferWithCursor, states: &[SplashEffect]) {
-    for it in states {
-        if !it.enable {
-            continue;
-        }
+    for it in &frame.touches {
         wrapped_buffer.write(
-            RendSplashEffect {
-                rend_type: 6,
-                x: it.x as f32,
-                y: it.y as f32,
-                frame: ((30.0 * it.progress).floor() as i8).clamp(0, 29),
-                tint_type: it.tint_type,
+            RendPoint {
+                rend_type: 4,
+                x: it.x,
+                y: it.y,
             }
             .to_bytes(),
         );
     }
+    wrapped_buffer.write(&[0]);
 }
 
-fn write_click_effects(wrapped_buffer: &mut impl BufferWithCursor, states: &[HitEffect]) {
-    for it in states {
-        if !it.enable {
-            continue;
+fn write_frame_header(wrapped_buffer: &mut impl BufferWithCursor, frame: &Frame) {
+    let counts = [
+        frame.lines.len(),
+        frame.notes.len(),
+        frame.click_effects.len(),
+        frame.touches.len(),
+        1,
+        frame.splash_effects.len(),
+        1,
+    ]
+    .map(|it| it as u32);
+    wrapped_buffer.write(
+        RendFrameHeader {
+            magic: RENDER_MAGIC,
+            version: RENDER_PROTOCOL_VERSION,
+            endianness: u8::from(cfg!(target_endian = "big")),
+            frame_time: frame.frame_time,
+            counts,
         }
-        wrapped_buffer.write(
-            RendClickEffect {
-                rend_type: 3,
-                x: it.x as f32,
-                y: it.y as f32,
-                frame: ((30.0 * it.progress).floor() as i8).clamp(0, 29),
-                tint_type: it.tint_type,
-            }
-            .to_bytes(),
-        );
+        .to_bytes(),
+    );
+}
+
+fn effect_draw(x: f64, y: f64, progress: f64, tint_type: i8) -> EffectDraw {
+    EffectDraw {
+        x: x as f32,
+        y: y as f32,
+        frame: ((30.0 * progress).floor() as i8).clamp(0, 29),
+        tint_type,
     }
 }
 
-fn write_line(wrapped_buffer: &mut impl BufferWithCursor, state: &LineState) {
+fn process_line(state: &LineState, tint: [u8; 3]) -> Option<LineDraw> {
     fn eq(a: f64, b: f64) -> bool {
         (a - b).abs() <= f64::EPSILON
     }
@@ -162,7 +287,7 @@ fn write_line(wrapped_buffer: &mut impl BufferWithCursor, state: &LineState) {
     let p2 =
         math::get_cross_point_with_screen(state.x, state.y, math::fix_degree(state.rotate + 180.0));
     if state.alpha <= 0.0 {
-        return;
+        return None;
     }
     if (((eq(p1.x, 0.0) && eq(p2.x, math::WORLD_WIDTH))
         || (eq(p2.x, 0.0) && eq(p1.x, math::WORLD_WIDTH)))
@@ -173,43 +298,24 @@ fn write_line(wrapped_buffer: &mut impl BufferWithCursor, state: &LineState) {
             && ((p1.x <= 0.0 && p2.x <= 0.0)
                 || (p1.x >= math::WORLD_WIDTH && p2.x >= math::WORLD_WIDTH)))
     {
-        return;
+        return None;
     }
-    let line = renders::RendLine {
-        rend_type: 1,
+    Some(LineDraw {
         x1: p1.x as f32,
         y1: p1.y as f32,
         x2: p2.x as f32,
         y2: p2.y as f32,
         alpha: state.alpha as f32,
-    };
-    let line_slice = line.to_bytes();
-    wrapped_buffer.write(line_slice);
-}
-
-fn write_notes(
-    wrapped_buffer: &mut impl BufferWithCursor,
-    states: &[LineState],
-    offset: &DrawImageOffset,
-) {
-    let notes = states
-        .iter()
-        .fold((Vec::new(), Vec::new()), |(v1, v2), it| {
-            process_notes(it, offset, v1, v2)
-        });
-    notes
-        .1
-        .iter()
-        .chain(notes.0.iter())
-        .for_each(|it| wrapped_buffer.write(it.to_bytes()));
+        color: state.color.unwrap_or(tint),
+    })
 }
 
 fn process_notes(
     state: &LineState,
     offset: &DrawImageOffset,
-    mut vec: Vec<RendNote>,
-    mut hold_vec: Vec<RendNote>,
-) -> (Vec<RendNote>, Vec<RendNote>) {
+    mut vec: Vec<NoteDraw>,
+    mut hold_vec: Vec<NoteDraw>,
+) -> (Vec<NoteDraw>, Vec<NoteDraw>) {
     process_notes_half(
         state,
         offset,
@@ -238,8 +344,8 @@ fn process_notes_half(
     offset: &DrawImageOffset,
     notes: &[NoteState],
     reverse: bool,
-    out: &mut Vec<RendNote>,
-    out_hold: &mut Vec<RendNote>,
+    out: &mut Vec<NoteDraw>,
+    out_hold: &mut Vec<NoteDraw>,
 ) {
     let iter = notes.iter();
     for note_state in iter {
@@ -266,7 +372,7 @@ fn process_normal_note(
     reverse: bool,
     line_state: &LineState,
     note_state: &NoteState,
-    out: &mut Vec<RendNote>,
+    out: &mut Vec<NoteDraw>,
 ) {
     let LineState {
         x,
@@ -288,7 +394,6 @@ fn process_normal_note(
         highlight,
         ..
     } = note_state;
-    let should_high_light = i8::from(*highlight);
     let delta_y = floor_position - line_y;
     if *time <= line_state.tick_time as i32 || *line_y > *floor_position + 0.001 {
         return;
@@ -304,14 +409,13 @@ fn process_normal_note(
     if !check_in_bound(x, y) {
         return;
     }
-    out.push(RendNote {
-        rend_type: 2,
+    out.push(NoteDraw {
         note_type: (*note_type).into(),
         x: x as f32,
         y: y as f32,
         rotate: *rotate as f32,
         height: 0.0,
-        high_light: should_high_light,
+        high_light: *highlight,
     });
 }
 
@@ -321,7 +425,7 @@ fn process_hold_note(
     line_state: &LineState,
     note_state: &NoteState,
     offset: &DrawImageOffset,
-    out_hold: &mut Vec<RendNote>,
+    out_hold: &mut Vec<NoteDraw>,
 ) {
     let LineState {
         x,
@@ -345,7 +449,6 @@ fn process_hold_note(
         highlight,
         ..
     } = note_state;
-    let should_high_light = i8::from(*highlight);
     let seconds_per_tick = 60.0 / bpm / 32.0;
     let head_position = floor_position - line_y;
     let body_height = hold_time * speed * seconds_per_tick - 0.0f64.max(-head_position);
@@ -403,33 +506,30 @@ fn process_hold_note(
                 offset.hold_end_height / 2.0
             }),
     );
-    out_hold.push(RendNote {
-        rend_type: 2,
+    out_hold.push(NoteDraw {
         note_type: 7,
         x: ex as f32,
         y: ey as f32,
         rotate: math::fix_degree(*rotate + if reverse { 180.0 } else { 0.0 }) as f32,
         height: 0.0,
-        high_light: 0,
+        high_light: false,
     });
-    out_hold.push(RendNote {
-        rend_type: 2,
+    out_hold.push(NoteDraw {
         note_type: 6,
         x: bx as f32,
         y: by as f32,
         rotate: math::fix_degree(*rotate + if reverse { 180.0 } else { 0.0 }) as f32,
         height: (body_height * math::UNIT_HEIGHT) as f32,
-        high_light: should_high_light,
+        high_light: *highlight,
     });
     if *time > *tick_time as i32 {
-        out_hold.push(RendNote {
-            rend_type: 2,
+        out_hold.push(NoteDraw {
             note_type: 5,
             x: hx as f32,
             y: hy as f32,
             rotate: math::fix_degree(*rotate + if reverse { 180.0 } else { 0.0 }) as f32,
             height: 0.0,
-            high_light: should_high_light,
+            high_light: *highlight,
         });
     }
 }
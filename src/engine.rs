@@ -0,0 +1,73 @@
+use crate::{
+    draw::DrawImageOffset,
+    input::TouchInfo,
+    judge_config::JudgeConfig,
+    play_mode::PlayMode,
+    replay::{PlayOrigin, Replay},
+    states::LineState,
+    states_effect::{HitEffect, SoundEffect, SplashEffect},
+    states_judge::JudgeEvent,
+    states_statistics::{ChartStatistics, ComboOrder, NoteIndex, StatisticsTally},
+};
+
+/// An owned playing session.
+///
+/// The engine holds every piece of state that is needed to play a single
+/// chart, so several charts can be played side by side, or the whole session
+/// can be moved to another thread.
+///
+/// The free functions of this crate operate on a default, thread-local
+/// instance of the engine.
+pub struct Engine {
+    pub(crate) draw_image_offset: DrawImageOffset,
+    pub(crate) flatten_note_index: Vec<NoteIndex>,
+    pub(crate) line_states: Vec<LineState>,
+    pub(crate) touch_states: [TouchInfo; 30],
+    pub(crate) hit_effect_pool: [HitEffect; 64],
+    pub(crate) splash_effect_pool: [SplashEffect; 256],
+    pub(crate) chart_statistics: ChartStatistics,
+    pub(crate) statistics_tally: StatisticsTally,
+    pub(crate) sound_pool: SoundEffect,
+    pub(crate) recompute_floor_position: bool,
+    pub(crate) judge_config: JudgeConfig,
+    pub(crate) combo_order: ComboOrder,
+    pub(crate) play_mode: PlayMode,
+    pub(crate) frame_time: f64,
+    pub(crate) judge_events: Vec<JudgeEvent>,
+    pub(crate) collect_judge_events: bool,
+    pub(crate) replay_recorder: Option<Replay>,
+    pub(crate) play_origin: PlayOrigin,
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Engine {
+            draw_image_offset: DrawImageOffset::default(),
+            flatten_note_index: Vec::new(),
+            line_states: Vec::new(),
+            touch_states: std::array::from_fn(|_| TouchInfo::default()),
+            hit_effect_pool: std::array::from_fn(|_| HitEffect::default()),
+            splash_effect_pool: std::array::from_fn(|_| SplashEffect::default()),
+            chart_statistics: ChartStatistics::default(),
+            statistics_tally: StatisticsTally::default(),
+            sound_pool: SoundEffect::default(),
+            recompute_floor_position: false,
+            judge_config: JudgeConfig::default(),
+            combo_order: ComboOrder::default(),
+            play_mode: PlayMode::default(),
+            frame_time: 0.0,
+            judge_events: Vec::new(),
+            collect_judge_events: false,
+            replay_recorder: None,
+            play_origin: PlayOrigin::Loaded,
+        }
+    }
+}
+
+impl Engine {
+    /// Create an empty engine without any chart loaded
+    #[must_use]
+    pub fn new() -> Self {
+        Engine::default()
+    }
+}
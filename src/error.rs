@@ -0,0 +1,97 @@
+use std::fmt;
+
+use crate::chart_validation::{Diagnostic, Severity};
+
+/// The error returned when a chart can not be loaded.
+///
+/// The number of judge lines is not limited, so there is no error for a chart
+/// with too many lines.
+#[derive(Debug)]
+pub enum ChartLoadError {
+    /// The input is not valid JSON, or does not match the chart structure
+    Json(serde_json::Error),
+
+    /// The chart does not contain the `formatVersion` field
+    MissingFormatVersion,
+
+    /// The `formatVersion` of the chart is not supported
+    UnsupportedVersion(i64),
+
+    /// A note has a type other than 1, 2, 3 or 4
+    InvalidNoteType {
+        /// The value found in the chart
+        value: serde_json::Value,
+        /// The JSON path to the note, e.g. `judgeLineList[0].notesAbove[3]`
+        path: String,
+    },
+
+    /// A judge line has a BPM that is zero, negative or not a number
+    NonPositiveBpm {
+        /// The index of the judge line
+        line: usize,
+        /// The BPM found in the chart
+        bpm: f64,
+    },
+
+    /// The chart is well-formed but semantically invalid. Contains every
+    /// diagnostic found, at least one of them is an error.
+    Validation(Vec<Diagnostic>),
+
+    /// The text of a `PhiEdit` chart can not be parsed
+    InvalidPec {
+        /// The line number in the text, starting from 1
+        line: usize,
+        /// What is wrong with the line
+        message: String,
+    },
+}
+
+impl fmt::Display for ChartLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChartLoadError::Json(err) => write!(f, "invalid chart json: {err}"),
+            ChartLoadError::MissingFormatVersion => write!(f, "missing field `formatVersion`"),
+            ChartLoadError::UnsupportedVersion(version) => {
+                write!(f, "unsupported format version: {version}")
+            }
+            ChartLoadError::InvalidNoteType { value, path } => {
+                write!(
+                    f,
+                    "invalid note type {value} at {path}, expected 1, 2, 3 or 4"
+                )
+            }
+            ChartLoadError::NonPositiveBpm { line, bpm } => {
+                write!(f, "judge line {line} has a non-positive bpm: {bpm}")
+            }
+            ChartLoadError::Validation(diagnostics) => {
+                let mut errors = diagnostics
+                    .iter()
+                    .filter(|it| it.severity == Severity::Error);
+                match errors.next() {
+                    Some(first) => {
+                        write!(f, "invalid chart: {first} ({} more errors)", errors.count())
+                    }
+                    None => write!(f, "invalid chart"),
+                }
+            }
+            ChartLoadError::InvalidPec { line, message } => {
+                write!(f, "invalid pec chart at line {line}: {message}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ChartLoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ChartLoadError::Json(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<serde_json::Error> for ChartLoadError {
+    fn from(value: serde_json::Error) -> Self {
+        ChartLoadError::Json(value)
+    }
+}
@@ -0,0 +1,389 @@
+//! The C ABI of the library.
+//!
+//! Every function operates on the thread-local engine, so all calls must be
+//! made from the same thread. The matching declarations are in
+//! `include/phasetida_core.h`, which `tests/ffi.rs` checks against this
+//! module and the render schema.
+//!
+//! Functions return a `PhasetidaStatus`, the message of the last error can be
+//! read with `phasetida_last_error`.
+
+use std::{
+    cell::RefCell,
+    panic::{self, AssertUnwindSafe},
+};
+
+use crate::{
+    ENGINE, draw::BufferWithCursor, error::ChartLoadError, play_mode::PlayMode, renders,
+    states::SeekPolicy,
+};
+
+thread_local! {
+    static LAST_ERROR: RefCell<String> = const { RefCell::new(String::new()) };
+}
+
+/// The status returned by every function of the C ABI
+#[repr(i32)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PhasetidaStatus {
+    /// The call succeeded
+    Ok = 0,
+
+    /// A required pointer is null
+    NullPointer = 1,
+
+    /// The chart buffer is not valid UTF-8
+    InvalidUtf8 = 2,
+
+    /// The chart is not valid JSON, or does not match the chart structure
+    InvalidJson = 3,
+
+    /// The format version of the chart is missing or not supported
+    UnsupportedVersion = 4,
+
+    /// The chart can be parsed but is invalid
+    InvalidChart = 5,
+
+    /// The text of a `PhiEdit` chart can not be parsed
+    InvalidPec = 6,
+
+    /// The buffer is too small, the required length is written to `out_len`
+    BufferTooSmall = 7,
+
+    /// The library panicked, the engine should be cleared
+    Panic = 8,
+
+    /// An argument is out of its range
+    InvalidArgument = 9,
+}
+
+/// The metadata of a loaded chart
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct PhasetidaMetadata {
+    /// The estimated length of the chart
+    pub length_in_second: f64,
+
+    /// The offset of the chart
+    pub offset: f64,
+
+    /// The format version of the chart, the `RPEVersion` of an RPE chart, or 0
+    /// for a PEC chart
+    pub format_version: i32,
+}
+
+impl From<&ChartLoadError> for PhasetidaStatus {
+    fn from(value: &ChartLoadError) -> Self {
+        match value {
+            ChartLoadError::Json(_) => PhasetidaStatus::InvalidJson,
+            ChartLoadError::MissingFormatVersion | ChartLoadError::UnsupportedVersion(_) => {
+                PhasetidaStatus::UnsupportedVersion
+            }
+            ChartLoadError::InvalidNoteType { .. }
+            | ChartLoadError::NonPositiveBpm { .. }
+            | ChartLoadError::Validation(_) => PhasetidaStatus::InvalidChart,
+            ChartLoadError::InvalidPec { .. } => PhasetidaStatus::InvalidPec,
+        }
+    }
+}
+
+struct VecBuffer(Vec<u8>);
+
+impl BufferWithCursor for VecBuffer {
+    fn write(&mut self, slice: &[u8]) {
+        self.0.extend_from_slice(slice);
+    }
+}
+
+fn set_last_error(message: String) {
+    LAST_ERROR.with_borrow_mut(|it| *it = message);
+}
+
+/// Run `f`, turning a panic into `PhasetidaStatus::Panic` so it never unwinds
+/// into the caller
+fn guard(f: impl FnOnce() -> PhasetidaStatus) -> PhasetidaStatus {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(status) => {
+            if status == PhasetidaStatus::Ok {
+                set_last_error(String::new());
+            }
+            status
+        }
+        Err(payload) => {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(ToString::to_string)
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_default();
+            set_last_error(format!("panicked: {message}"));
+            PhasetidaStatus::Panic
+        }
+    }
+}
+
+/// Copy `bytes` to the buffer of the caller
+///
+/// # Safety
+///
+/// `buf` must be valid for writes of `capacity` bytes, and `out_len` must be
+/// valid for a write.
+unsafe fn copy_out(
+    bytes: &[u8],
+    buf: *mut u8,
+    capacity: usize,
+    out_len: *mut usize,
+) -> PhasetidaStatus {
+    if out_len.is_null() {
+        return PhasetidaStatus::NullPointer;
+    }
+    unsafe { out_len.write(bytes.len()) };
+    if bytes.len() > capacity {
+        return PhasetidaStatus::BufferTooSmall;
+    }
+    if bytes.is_empty() {
+        return PhasetidaStatus::Ok;
+    }
+    if buf.is_null() {
+        return PhasetidaStatus::NullPointer;
+    }
+    unsafe { std::ptr::copy_nonoverlapping(bytes.as_ptr(), buf, bytes.len()) };
+    PhasetidaStatus::Ok
+}
+
+/// Load a chart from a UTF-8 buffer, replacing the current one.
+///
+/// The chart is read as JSON (official or RPE) if it starts with `{`, and as
+/// a `PhiEdit` chart otherwise. A leading UTF-8 byte order mark is skipped.
+/// `out_metadata` may be null.
+///
+/// # Safety
+///
+/// `data` must be valid for reads of `len` bytes, and `out_metadata` must be
+/// null or valid for a write.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn phasetida_load_chart(
+    data: *const u8,
+    len: usize,
+    out_metadata: *mut PhasetidaMetadata,
+) -> PhasetidaStatus {
+    guard(|| {
+        if data.is_null() {
+            return PhasetidaStatus::NullPointer;
+        }
+        let bytes = unsafe { std::slice::from_raw_parts(data, len) };
+        let Ok(text) = std::str::from_utf8(bytes) else {
+            set_last_error("chart is not valid UTF-8".to_string());
+            return PhasetidaStatus::InvalidUtf8;
+        };
+        let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+        let result = ENGINE.with_borrow_mut(|it| {
+            if text.trim_start().starts_with('{') {
+                it.init_from_json(text)
+            } else {
+                it.init_from_pec(text)
+            }
+        });
+        match result {
+            Ok(metadata) => {
+                if !out_metadata.is_null() {
+                    unsafe {
+                        out_metadata.write(PhasetidaMetadata {
+                            length_in_second: metadata.length_in_second,
+                            offset: metadata.offset,
+                            format_version: metadata.format_version,
+                        });
+                    }
+                }
+                PhasetidaStatus::Ok
+            }
+            Err(err) => {
+                set_last_error(err.to_string());
+                PhasetidaStatus::from(&err)
+            }
+        }
+    })
+}
+
+/// Preload the heights of the hold note images, see `load_image_offset`
+#[unsafe(no_mangle)]
+pub extern "C" fn phasetida_load_image_offset(
+    hold_head_height: f64,
+    hold_head_highlight_height: f64,
+    hold_end_height: f64,
+    hold_end_highlight_height: f64,
+) -> PhasetidaStatus {
+    guard(|| {
+        ENGINE.with_borrow_mut(|it| {
+            it.load_image_offset(
+                hold_head_height,
+                hold_head_highlight_height,
+                hold_end_height,
+                hold_end_highlight_height,
+            );
+        });
+        PhasetidaStatus::Ok
+    })
+}
+
+/// Tick all states, see `tick_all`
+#[unsafe(no_mangle)]
+pub extern "C" fn phasetida_tick(
+    time_in_second: f64,
+    delta_time_in_second: f64,
+    auto: bool,
+) -> PhasetidaStatus {
+    guard(|| {
+        ENGINE.with_borrow_mut(|it| it.tick_all(time_in_second, delta_time_in_second, auto));
+        PhasetidaStatus::Ok
+    })
+}
+
+/// Set a touch point as enabled
+#[unsafe(no_mangle)]
+pub extern "C" fn phasetida_touch_down(id: usize, x: f32, y: f32) -> PhasetidaStatus {
+    guard(|| {
+        ENGINE.with_borrow_mut(|it| it.set_touch_down(id, x, y));
+        PhasetidaStatus::Ok
+    })
+}
+
+/// Move a touch point
+#[unsafe(no_mangle)]
+pub extern "C" fn phasetida_touch_move(id: usize, x: f32, y: f32) -> PhasetidaStatus {
+    guard(|| {
+        ENGINE.with_borrow_mut(|it| it.set_touch_move(id, x, y));
+        PhasetidaStatus::Ok
+    })
+}
+
+/// Set a touch point as disabled
+#[unsafe(no_mangle)]
+pub extern "C" fn phasetida_touch_up(id: usize) -> PhasetidaStatus {
+    guard(|| {
+        ENGINE.with_borrow_mut(|it| it.set_touch_up(id));
+        PhasetidaStatus::Ok
+    })
+}
+
+/// Reset the state of notes, see `reset_note_state`
+#[unsafe(no_mangle)]
+pub extern "C" fn phasetida_reset(before_time_in_second: f64) -> PhasetidaStatus {
+    guard(|| {
+        ENGINE.with_borrow_mut(|it| it.reset_note_state(before_time_in_second));
+        PhasetidaStatus::Ok
+    })
+}
+
+/// Move the play to `time_in_second`, see `seek`. `policy` is 0 for practice,
+/// 1 for autoplay fill and 2 for reset.
+#[unsafe(no_mangle)]
+pub extern "C" fn phasetida_seek(time_in_second: f64, policy: i32) -> PhasetidaStatus {
+    guard(|| {
+        let policy = match policy {
+            0 => SeekPolicy::Practice,
+            1 => SeekPolicy::AutoplayFill,
+            2 => SeekPolicy::Reset,
+            _ => {
+                set_last_error(format!("unknown seek policy: {policy}"));
+                return PhasetidaStatus::InvalidArgument;
+            }
+        };
+        ENGINE.with_borrow_mut(|it| it.seek(time_in_second, policy));
+        PhasetidaStatus::Ok
+    })
+}
+
+/// Set the rules that decide when a run fails, see `PlayMode`. `mode` is 0
+/// for normal, 1 for sudden death, 2 for perfect only and 3 for no fail.
+#[unsafe(no_mangle)]
+pub extern "C" fn phasetida_set_play_mode(mode: i32) -> PhasetidaStatus {
+    guard(|| {
+        let mode = match mode {
+            0 => PlayMode::Normal,
+            1 => PlayMode::SuddenDeath,
+            2 => PlayMode::PerfectOnly,
+            3 => PlayMode::NoFail,
+            _ => {
+                set_last_error(format!("unknown play mode: {mode}"));
+                return PhasetidaStatus::InvalidArgument;
+            }
+        };
+        ENGINE.with_borrow_mut(|it| it.set_play_mode(mode));
+        PhasetidaStatus::Ok
+    })
+}
+
+/// Set the colour of a judge line, replacing the tint of the combo status
+#[unsafe(no_mangle)]
+pub extern "C" fn phasetida_set_line_color(line: usize, r: u8, g: u8, b: u8) -> PhasetidaStatus {
+    guard(|| {
+        ENGINE.with_borrow_mut(|it| it.set_line_color(line, Some([r, g, b])));
+        PhasetidaStatus::Ok
+    })
+}
+
+/// Restore the tint of the combo status on a judge line
+#[unsafe(no_mangle)]
+pub extern "C" fn phasetida_reset_line_color(line: usize) -> PhasetidaStatus {
+    guard(|| {
+        ENGINE.with_borrow_mut(|it| it.set_line_color(line, None));
+        PhasetidaStatus::Ok
+    })
+}
+
+/// Clear the loaded chart and every state
+#[unsafe(no_mangle)]
+pub extern "C" fn phasetida_clear() -> PhasetidaStatus {
+    guard(|| {
+        ENGINE.with_borrow_mut(crate::engine::Engine::clear);
+        PhasetidaStatus::Ok
+    })
+}
+
+/// Render the states into the buffer of the caller, in the format written by
+/// `process_state_to_drawable`.
+///
+/// The length of the frame is always written to `out_len`. If it is larger
+/// than `capacity`, nothing is written to `buf` and
+/// `PhasetidaStatus::BufferTooSmall` is returned.
+///
+/// # Safety
+///
+/// `buf` must be valid for writes of `capacity` bytes, and `out_len` must be
+/// valid for a write.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn phasetida_render(
+    buf: *mut u8,
+    capacity: usize,
+    out_len: *mut usize,
+) -> PhasetidaStatus {
+    guard(|| {
+        let mut buffer = VecBuffer(Vec::new());
+        ENGINE.with_borrow(|it| it.process_state_to_drawable(&mut buffer));
+        unsafe { copy_out(&buffer.0, buf, capacity, out_len) }
+    })
+}
+
+/// Get the version of the render stream produced by this library, compare it
+/// with the version in the frame header to detect a mismatch
+#[unsafe(no_mangle)]
+pub extern "C" fn phasetida_render_protocol_version() -> u16 {
+    renders::RENDER_PROTOCOL_VERSION
+}
+
+/// Copy the UTF-8 message of the last error into the buffer of the caller,
+/// without a trailing nul. The message is empty after a successful call.
+///
+/// # Safety
+///
+/// `buf` must be valid for writes of `capacity` bytes, and `out_len` must be
+/// valid for a write.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn phasetida_last_error(
+    buf: *mut u8,
+    capacity: usize,
+    out_len: *mut usize,
+) -> PhasetidaStatus {
+    let message = LAST_ERROR.with_borrow(Clone::clone);
+    unsafe { copy_out(message.as_bytes(), buf, capacity, out_len) }
+}
@@ -0,0 +1,129 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{ENGINE, engine::Engine, replay::ReplayEvent, states::NoteScore};
+
+/// The window of a grade in seconds, measured from the time of the note
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct JudgeWindow {
+    /// How early a hit can be
+    pub early: f64,
+
+    /// How late a hit can be
+    pub late: f64,
+}
+
+impl JudgeWindow {
+    /// Create a window that is as wide early as late
+    #[must_use]
+    pub const fn symmetric(seconds: f64) -> JudgeWindow {
+        JudgeWindow {
+            early: seconds,
+            late: seconds,
+        }
+    }
+
+    fn contains(self, delta_in_second: f64) -> bool {
+        if delta_in_second < 0.0 {
+            -delta_in_second <= self.early
+        } else {
+            delta_in_second <= self.late
+        }
+    }
+}
+
+/// The timing windows used to grade a hit.
+///
+/// A hit is graded by the first window that contains it, a hit outside of
+/// every window is a miss. Each window should contain the previous one.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct JudgeConfig {
+    /// The window of a perfect hit
+    pub perfect: JudgeWindow,
+
+    /// The window of a good hit
+    pub good: JudgeWindow,
+
+    /// The window of a bad hit
+    pub bad: JudgeWindow,
+}
+
+impl JudgeConfig {
+    /// The windows of the official game, 80 ms perfect, 160 ms good and 180 ms
+    /// bad
+    pub const OFFICIAL: JudgeConfig = JudgeConfig::symmetric(0.08, 0.16, 0.18);
+
+    /// The narrower windows of the challenge mode, 40 ms perfect, 75 ms good
+    /// and 140 ms bad
+    pub const STRICT: JudgeConfig = JudgeConfig::symmetric(0.04, 0.075, 0.14);
+
+    /// Wider windows for accessibility, 1.5 times the official ones
+    pub const LENIENT: JudgeConfig = JudgeConfig::symmetric(0.12, 0.24, 0.27);
+
+    /// Create windows that are as wide early as late
+    #[must_use]
+    pub const fn symmetric(perfect: f64, good: f64, bad: f64) -> JudgeConfig {
+        JudgeConfig {
+            perfect: JudgeWindow::symmetric(perfect),
+            good: JudgeWindow::symmetric(good),
+            bad: JudgeWindow::symmetric(bad),
+        }
+    }
+
+    /// Create windows with separate early and late widths, each given as
+    /// `(perfect, good, bad)` in seconds
+    #[must_use]
+    pub const fn asymmetric(early: (f64, f64, f64), late: (f64, f64, f64)) -> JudgeConfig {
+        JudgeConfig {
+            perfect: JudgeWindow {
+                early: early.0,
+                late: late.0,
+            },
+            good: JudgeWindow {
+                early: early.1,
+                late: late.1,
+            },
+            bad: JudgeWindow {
+                early: early.2,
+                late: late.2,
+            },
+        }
+    }
+
+    /// Grade a hit, `delta_in_second` is negative when the hit is early
+    pub(crate) fn grade(&self, delta_in_second: f64) -> NoteScore {
+        if self.perfect.contains(delta_in_second) {
+            NoteScore::Perfect
+        } else if self.good.contains(delta_in_second) {
+            NoteScore::Good
+        } else if self.bad.contains(delta_in_second) {
+            NoteScore::Bad
+        } else {
+            NoteScore::Miss
+        }
+    }
+}
+
+impl Default for JudgeConfig {
+    fn default() -> Self {
+        JudgeConfig::OFFICIAL
+    }
+}
+
+impl Engine {
+    /// Set the timing windows used to judge notes
+    pub fn set_judge_config(&mut self, config: JudgeConfig) {
+        self.record(ReplayEvent::SetJudgeConfig(config));
+        self.judge_config = config;
+    }
+
+    /// Get the timing windows used to judge notes
+    #[must_use]
+    pub fn judge_config(&self) -> JudgeConfig {
+        self.judge_config
+    }
+}
+
+/// Set the timing windows used to judge notes
+pub fn set_judge_config(config: JudgeConfig) {
+    ENGINE.with_borrow_mut(|it| it.set_judge_config(config));
+}
@@ -1,12 +1,18 @@
 //! A simple library that renders the official chart format of Phigros to dense
 //! structured data.
 //!
+//! Charts of Re:PhiEdit and `PhiEdit` are converted to the official format on
+//! loading.
+//!
 //! To use this library, you need to implement trait `BufferWithCursor` to
 //! receive the structured data.
 //!
 //! This library only maintains states, you need to provide time parameter for
 //! ticking the states
 //!
+//! Each `Engine` owns the states of one playing session. The free functions
+//! operate on a default engine that is local to the current thread.
+//!
 //!
 
 #![deny(clippy::pedantic)]
@@ -15,11 +21,25 @@
 
 use std::cell::RefCell;
 
+mod bytes;
 mod chart;
+mod chart_events;
+mod chart_pec;
+mod chart_rpe;
+mod chart_validation;
 mod draw;
+mod engine;
+mod error;
+pub mod ffi;
 mod input;
+mod judge_config;
 mod math;
-mod renders;
+mod play_mode;
+mod ranking;
+pub mod renders;
+mod replay;
+mod simulation;
+mod snapshot;
 mod states;
 mod states_effect;
 mod states_initializing;
@@ -29,32 +49,95 @@ mod states_lines;
 mod states_statistics;
 
 thread_local! {
-    pub(crate) static DRAW_IMAGE_OFFSET:RefCell<draw::DrawImageOffset> = RefCell::new(draw::DrawImageOffset::default());
-    pub(crate) static FLATTEN_NOTE_INDEX:RefCell<Vec<states_statistics::NoteIndex>>= const{RefCell::new(Vec::<_>::new())};
-    pub(crate) static LINE_STATES: RefCell<[states::LineState;50]> = RefCell::new(std::array::from_fn(|_|states::LineState::default()));
-    pub(crate) static TOUCH_STATES: RefCell<[input::TouchInfo; 30]> = RefCell::new(std::array::from_fn(|_|input::TouchInfo::default()));
-    pub(crate) static HIT_EFFECT_POOL: RefCell<[states_effect::HitEffect; 64]> = RefCell::new(std::array::from_fn(|_|states_effect::HitEffect::default()));
-    pub(crate) static SPLASH_EFFECT_POOL : RefCell<[states_effect::SplashEffect;256]> = RefCell::new(std::array::from_fn(|_|states_effect::SplashEffect::default()));
-    pub(crate) static CHART_STATISTICS: RefCell<states_statistics::ChartStatistics> = RefCell::new(states_statistics::ChartStatistics::default());
-    pub(crate) static SOUND_POOL: RefCell<states_effect::SoundEffect> = RefCell::new(states_effect::SoundEffect::default());
+    pub(crate) static ENGINE: RefCell<engine::Engine> = RefCell::new(engine::Engine::default());
 }
 
 pub use chart::Chart;
 pub use chart::ChartRaw;
+pub use chart::ConversionIssue;
+pub use chart::ConversionReport;
+pub use chart::NoteType;
+pub use chart_validation::Diagnostic;
+pub use chart_validation::DiagnosticTarget;
+pub use chart_validation::FloorPositionChange;
+pub use chart_validation::FloorPositionReport;
+pub use chart_validation::Severity;
+pub use chart_validation::validate;
 pub use draw::BufferWithCursor;
+pub use engine::Engine;
+pub use error::ChartLoadError;
+pub use judge_config::JudgeConfig;
+pub use judge_config::JudgeWindow;
+pub use play_mode::PlayMode;
+pub use ranking::ChartResult;
+pub use ranking::RKS_MIN_ACCURACY;
+pub use ranking::RksFormula;
+pub use ranking::RksSummary;
+pub use renders::RENDER_MAGIC;
+pub use renders::RENDER_PROTOCOL_VERSION;
+pub use renders::RENDER_SCHEMA;
+pub use replay::REPLAY_FORMAT_VERSION;
+pub use replay::REPLAY_MAGIC;
+pub use replay::Replay;
+pub use replay::ReplayError;
+pub use replay::ReplayEvent;
+pub use replay::ReplayPlayer;
+pub use simulation::NoteJudgment;
+pub use simulation::SimulationAnomaly;
+pub use simulation::SimulationError;
+pub use simulation::SimulationInput;
+pub use simulation::SimulationOptions;
+pub use simulation::SimulationReport;
+pub use snapshot::EngineSnapshot;
 pub use states::Metadata;
+pub use states::NoteScore;
+pub use states::SeekPolicy;
+pub use states_judge::JudgeEvent;
+pub use states_judge::JudgeEventKind;
+pub use states_statistics::ChartStatistics;
+pub use states_statistics::ComboOrder;
+pub use states_statistics::ComboStatus;
+pub use states_statistics::Rank;
+pub use states_statistics::TimingCount;
 
 pub use draw::load_image_offset;
 pub use draw::process_state_to_drawable;
+pub use draw::render_frame;
+pub use draw::set_line_color;
 
 pub use states_initializing::clear_states;
 pub use states_initializing::init_line_states;
 pub use states_initializing::init_line_states_from_json;
+pub use states_initializing::init_line_states_from_pec;
+pub use states_initializing::set_recompute_floor_position;
+
+pub use judge_config::set_judge_config;
+pub use play_mode::set_play_mode;
 
 pub use states_input::clear_touch;
 pub use states_input::set_touch_down;
 pub use states_input::set_touch_move;
 pub use states_input::set_touch_up;
 
+pub use states_statistics::get_chart_statistics;
+pub use states_statistics::get_hit_offsets;
+pub use states_statistics::set_combo_order;
+
+pub use states_judge::drain_judge_events;
+pub use states_judge::set_collect_judge_events;
+
 pub use states::reset_note_state;
+pub use states::seek;
+
+pub use replay::play_replay;
+pub use replay::start_recording;
+pub use replay::stop_recording;
+
+pub use simulation::simulate;
+
+pub use ranking::player_rks;
+pub use ranking::single_chart_rks;
+
+pub use snapshot::restore;
+pub use snapshot::snapshot;
 pub use states::tick_all;
@@ -206,3 +206,129 @@ pub fn check_rectangles_overlap(rect1: &Rect, rect2: &Rect) -> bool {
     }
     true
 }
+
+/// Evaluate the easing function `easing_type` of Re:PhiEdit at `x` in `[0, 1]`.
+///
+/// Returns `None` if the easing type is unknown.
+#[allow(clippy::too_many_lines)]
+pub fn ease(easing_type: i32, x: f64) -> Option<f64> {
+    use std::f64::consts::PI;
+    const C1: f64 = 1.70158;
+    const C2: f64 = C1 * 1.525;
+    const C3: f64 = C1 + 1.0;
+    const C4: f64 = 2.0 * PI / 3.0;
+    const C5: f64 = 2.0 * PI / 4.5;
+    fn bounce_out(x: f64) -> f64 {
+        const N1: f64 = 7.5625;
+        const D1: f64 = 2.75;
+        if x < 1.0 / D1 {
+            N1 * x * x
+        } else if x < 2.0 / D1 {
+            let x = x - 1.5 / D1;
+            N1 * x * x + 0.75
+        } else if x < 2.5 / D1 {
+            let x = x - 2.25 / D1;
+            N1 * x * x + 0.9375
+        } else {
+            let x = x - 2.625 / D1;
+            N1 * x * x + 0.984_375
+        }
+    }
+    let value = match easing_type {
+        0 | 1 => x,
+        2 => (x * PI / 2.0).sin(),
+        3 => 1.0 - (x * PI / 2.0).cos(),
+        4 => 1.0 - (1.0 - x).powi(2),
+        5 => x.powi(2),
+        6 => -((PI * x).cos() - 1.0) / 2.0,
+        7 => {
+            if x < 0.5 {
+                2.0 * x.powi(2)
+            } else {
+                1.0 - (-2.0 * x + 2.0).powi(2) / 2.0
+            }
+        }
+        8 => 1.0 - (1.0 - x).powi(3),
+        9 => x.powi(3),
+        10 => 1.0 - (1.0 - x).powi(4),
+        11 => x.powi(4),
+        12 => {
+            if x < 0.5 {
+                4.0 * x.powi(3)
+            } else {
+                1.0 - (-2.0 * x + 2.0).powi(3) / 2.0
+            }
+        }
+        13 => {
+            if x < 0.5 {
+                8.0 * x.powi(4)
+            } else {
+                1.0 - (-2.0 * x + 2.0).powi(4) / 2.0
+            }
+        }
+        14 => 1.0 - (1.0 - x).powi(5),
+        15 => x.powi(5),
+        16 => {
+            if x >= 1.0 {
+                1.0
+            } else {
+                1.0 - 2f64.powf(-10.0 * x)
+            }
+        }
+        17 => {
+            if x <= 0.0 {
+                0.0
+            } else {
+                2f64.powf(10.0 * x - 10.0)
+            }
+        }
+        18 => (1.0 - (x - 1.0).powi(2)).sqrt(),
+        19 => 1.0 - (1.0 - x.powi(2)).sqrt(),
+        20 => 1.0 + C3 * (x - 1.0).powi(3) + C1 * (x - 1.0).powi(2),
+        21 => C3 * x.powi(3) - C1 * x.powi(2),
+        22 => {
+            if x < 0.5 {
+                (1.0 - (1.0 - (2.0 * x).powi(2)).sqrt()) / 2.0
+            } else {
+                f64::midpoint((1.0 - (-2.0 * x + 2.0).powi(2)).sqrt(), 1.0)
+            }
+        }
+        23 => {
+            if x < 0.5 {
+                ((2.0 * x).powi(2) * ((C2 + 1.0) * 2.0 * x - C2)) / 2.0
+            } else {
+                f64::midpoint(
+                    (2.0 * x - 2.0).powi(2) * ((C2 + 1.0) * (x * 2.0 - 2.0) + C2),
+                    2.0,
+                )
+            }
+        }
+        24 => match x {
+            x if x <= 0.0 => 0.0,
+            x if x >= 1.0 => 1.0,
+            x => 2f64.powf(-10.0 * x) * ((x * 10.0 - 0.75) * C4).sin() + 1.0,
+        },
+        25 => match x {
+            x if x <= 0.0 => 0.0,
+            x if x >= 1.0 => 1.0,
+            x => -(2f64.powf(10.0 * x - 10.0)) * ((x * 10.0 - 10.75) * C4).sin(),
+        },
+        26 => bounce_out(x),
+        27 => 1.0 - bounce_out(1.0 - x),
+        28 => {
+            if x < 0.5 {
+                (1.0 - bounce_out(1.0 - 2.0 * x)) / 2.0
+            } else {
+                f64::midpoint(1.0, bounce_out(2.0 * x - 1.0))
+            }
+        }
+        29 => match x {
+            x if x <= 0.0 => 0.0,
+            x if x >= 1.0 => 1.0,
+            x if x < 0.5 => -(2f64.powf(20.0 * x - 10.0) * ((20.0 * x - 11.125) * C5).sin()) / 2.0,
+            x => (2f64.powf(-20.0 * x + 10.0) * ((20.0 * x - 11.125) * C5).sin()) / 2.0 + 1.0,
+        },
+        _ => return None,
+    };
+    Some(value)
+}
@@ -0,0 +1,61 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{ENGINE, engine::Engine, replay::ReplayEvent, states::NoteScore};
+
+/// The rules that decide when a run fails
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum PlayMode {
+    /// The run never fails
+    #[default]
+    Normal,
+
+    /// The run fails and ends on the first bad or missed note
+    SuddenDeath,
+
+    /// The run fails and ends on the first note that is not perfect
+    PerfectOnly,
+
+    /// The run is flagged as failed on the first bad or missed note, but
+    /// keeps being judged
+    NoFail,
+}
+
+impl PlayMode {
+    /// Whether a note of the grade fails the run
+    pub(crate) fn fails(self, grade: NoteScore) -> bool {
+        match self {
+            PlayMode::Normal => false,
+            PlayMode::SuddenDeath | PlayMode::NoFail => {
+                matches!(grade, NoteScore::Bad | NoteScore::Miss)
+            }
+            PlayMode::PerfectOnly => {
+                matches!(grade, NoteScore::Good | NoteScore::Bad | NoteScore::Miss)
+            }
+        }
+    }
+
+    /// Whether the notes stop being judged once the run failed
+    pub(crate) fn ends_run(self) -> bool {
+        matches!(self, PlayMode::SuddenDeath | PlayMode::PerfectOnly)
+    }
+}
+
+impl Engine {
+    /// Set the rules that decide when a run fails. A failure that already
+    /// happened is kept until the next seek.
+    pub fn set_play_mode(&mut self, mode: PlayMode) {
+        self.record(ReplayEvent::SetPlayMode(mode));
+        self.play_mode = mode;
+    }
+
+    /// Get the rules that decide when a run fails
+    #[must_use]
+    pub fn play_mode(&self) -> PlayMode {
+        self.play_mode
+    }
+}
+
+/// Set the rules that decide when a run fails
+pub fn set_play_mode(mode: PlayMode) {
+    ENGINE.with_borrow_mut(|it| it.set_play_mode(mode));
+}
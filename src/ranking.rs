@@ -0,0 +1,136 @@
+//! The ranking score (RKS) of the official game, for a single chart and
+//! aggregated over the best results of a player.
+
+use serde::{Deserialize, Serialize};
+
+use crate::states_statistics::{ChartStatistics, Rank};
+
+/// The accuracy below which a chart gives no ranking score
+pub const RKS_MIN_ACCURACY: f64 = 0.7;
+
+/// The final result of a play, as used for the ranking score
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct ChartResult {
+    /// The difficulty constant of the chart
+    pub constant: f64,
+
+    /// The accuracy of the play, from 0 to 1
+    pub accuracy: f64,
+
+    /// Whether the play scored 1,000,000
+    pub all_perfect: bool,
+}
+
+impl ChartResult {
+    /// Create the result of a finished play from its statistics
+    #[must_use]
+    pub fn from_statistics(constant: f64, statistics: &ChartStatistics) -> ChartResult {
+        ChartResult {
+            constant,
+            accuracy: statistics.accurate,
+            all_perfect: statistics.rank == Rank::Phi,
+        }
+    }
+
+    /// The ranking score of the result, see `single_chart_rks`
+    #[must_use]
+    pub fn rks(&self) -> f64 {
+        single_chart_rks(self.constant, self.accuracy)
+    }
+}
+
+/// How many results count towards the ranking score of a player
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct RksFormula {
+    /// The number of best results by ranking score
+    pub best: usize,
+
+    /// The number of best all perfect results, counted on top of the best
+    /// results even if they are among them
+    pub all_perfect: usize,
+}
+
+impl RksFormula {
+    /// The best 27 results and the best 3 all perfect results, used since
+    /// version 3.0 of the game
+    pub const B27_P3: RksFormula = RksFormula {
+        best: 27,
+        all_perfect: 3,
+    };
+
+    /// The best 19 results and the best all perfect result, used before
+    /// version 3.0 of the game
+    pub const B19_P1: RksFormula = RksFormula {
+        best: 19,
+        all_perfect: 1,
+    };
+}
+
+impl Default for RksFormula {
+    fn default() -> Self {
+        RksFormula::B27_P3
+    }
+}
+
+/// The ranking score of a player and the results it is made of
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct RksSummary {
+    /// The ranking score of the player
+    pub rks: f64,
+
+    /// The indices of the best results, highest first
+    pub best: Vec<usize>,
+
+    /// The indices of the best all perfect results, highest first
+    pub all_perfect: Vec<usize>,
+}
+
+/// The ranking score of a single chart.
+///
+/// It is `((100 * accuracy - 55) / 45)² * constant`, so an all perfect play
+/// gives the constant itself. A play below 70% accuracy gives nothing.
+#[must_use]
+pub fn single_chart_rks(constant: f64, accuracy: f64) -> f64 {
+    if accuracy < RKS_MIN_ACCURACY {
+        return 0.0;
+    }
+    let factor = (accuracy.min(1.0) * 100.0 - 55.0) / 45.0;
+    factor * factor * constant
+}
+
+/// Aggregate the results of a player into their ranking score.
+///
+/// The ranking scores of the best results and of the best all perfect results
+/// are averaged over `formula.best + formula.all_perfect`, missing results
+/// count as 0. Every result should be the best play of a different chart.
+#[must_use]
+pub fn player_rks(results: &[ChartResult], formula: RksFormula) -> RksSummary {
+    let mut ranked = results
+        .iter()
+        .map(ChartResult::rks)
+        .enumerate()
+        .collect::<Vec<_>>();
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+    let best = ranked
+        .iter()
+        .take(formula.best)
+        .copied()
+        .collect::<Vec<_>>();
+    let all_perfect = ranked
+        .iter()
+        .filter(|(index, _)| results[*index].all_perfect)
+        .take(formula.all_perfect)
+        .copied()
+        .collect::<Vec<_>>();
+    let count = formula.best + formula.all_perfect;
+    let total = best.iter().chain(&all_perfect).map(|it| it.1).sum::<f64>();
+    RksSummary {
+        rks: if count == 0 {
+            0.0
+        } else {
+            total / f64::from(count as u32)
+        },
+        best: best.into_iter().map(|it| it.0).collect(),
+        all_perfect: all_perfect.into_iter().map(|it| it.0).collect(),
+    }
+}
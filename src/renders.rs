@@ -1,15 +1,171 @@
+//! The dense render stream written by `process_state_to_drawable`, and a
+//! decoder for it.
+
+use serde::Serialize;
+
+use crate::{bytes::ByteReader, states_statistics::ChartStatistics};
+
+/// The magic bytes at the start of every rendered frame
+pub const RENDER_MAGIC: [u8; 4] = *b"PTDF";
+
+/// The version of the render stream, bumped whenever a record changes
+pub const RENDER_PROTOCOL_VERSION: u16 = 4;
+
+/// The layout of the render stream.
+///
+/// A frame is a header followed by records and a terminating `0` byte. Every
+/// record starts with its type byte, all fields are packed and in the byte
+/// order given by the header.
+pub const RENDER_SCHEMA: &str = "header:magic[u8;4],version:u16,endianness:u8,frame_time:f64,counts:[u32;7];\
+1:line:x1:f32,y1:f32,x2:f32,y2:f32,alpha:f32,color:[u8;3];\
+2:note:note_type:i8,x:f32,y:f32,rotate:f32,height:f32,high_light:i8;\
+3:click_effect:x:f32,y:f32,frame:i8,tint_type:i8;\
+4:touch:x:f32,y:f32;\
+5:statistics:combo:u32,max_combo:u32,score:f32,accurate:f32,rank:u8,combo_status:u8,failed:u8,failed_at:f32;\
+6:splash_effect:x:f32,y:f32,frame:i8,tint_type:i8;\
+7:sound:tap:i8,drag:i8,flick:i8;\
+0:end";
+
+/// The number of record types, excluding the terminator
+pub const RECORD_TYPE_COUNT: usize = 7;
+
+/// A frame rendered from the states, the structured form of the render stream
+#[derive(Serialize, Clone, Debug)]
+pub struct Frame {
+    /// The time of the last tick, in seconds
+    pub frame_time: f64,
+
+    /// The statistics of the play
+    pub statistics: ChartStatistics,
+
+    /// The visible judge lines
+    pub lines: Vec<LineDraw>,
+
+    /// The visible notes, hold notes first
+    pub notes: Vec<NoteDraw>,
+
+    /// The click effects
+    pub click_effects: Vec<EffectDraw>,
+
+    /// The splash particles
+    pub splash_effects: Vec<EffectDraw>,
+
+    /// The sounds to play in this frame
+    pub sound: SoundDraw,
+
+    /// The enabled touch points
+    pub touches: Vec<PointDraw>,
+}
+
+/// A judge line between two points on the edge of the screen
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+pub struct LineDraw {
+    /// The x of the first point
+    pub x1: f32,
+
+    /// The y of the first point
+    pub y1: f32,
+
+    /// The x of the second point
+    pub x2: f32,
+
+    /// The y of the second point
+    pub y2: f32,
+
+    /// The alpha of the line
+    pub alpha: f32,
+
+    /// The colour of the line as RGB, the tint of the combo status unless
+    /// the line has a colour of its own
+    pub color: [u8; 3],
+}
+
+/// A note, or a part of a hold note
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+pub struct NoteDraw {
+    /// 1 to 4 for tap, drag, hold and flick, 5 to 7 for the head, body and end
+    /// of a hold note
+    pub note_type: i8,
+
+    /// The x of the center
+    pub x: f32,
+
+    /// The y of the center
+    pub y: f32,
+
+    /// The rotation in degrees
+    pub rotate: f32,
+
+    /// The height of a hold body
+    pub height: f32,
+
+    /// Whether the note is highlighted
+    pub high_light: bool,
+}
+
+/// A click effect or a splash particle
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+pub struct EffectDraw {
+    /// The x of the center
+    pub x: f32,
+
+    /// The y of the center
+    pub y: f32,
+
+    /// The frame of the animation, 0 to 29
+    pub frame: i8,
+
+    /// 0 for perfect, 1 for good
+    pub tint_type: i8,
+}
+
+/// The number of sounds to play in a frame
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub struct SoundDraw {
+    /// The number of tap sounds
+    pub tap_sound: i8,
+
+    /// The number of drag sounds
+    pub drag_sound: i8,
+
+    /// The number of flick sounds
+    pub flick_sound: i8,
+}
+
+/// A touch point
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+pub struct PointDraw {
+    /// The x of the touch
+    pub x: f32,
+
+    /// The y of the touch
+    pub y: f32,
+}
+
+#[repr(C, packed)]
+pub(crate) struct RendFrameHeader {
+    pub magic: [u8; 4],
+    pub version: u16,
+    /// 0 for little endian, 1 for big endian
+    pub endianness: u8,
+    pub frame_time: f64,
+    /// The number of records of type `i + 1`
+    pub counts: [u32; RECORD_TYPE_COUNT],
+}
+
 #[repr(C, packed)]
-pub struct RendLine {
+pub(crate) struct RendLine {
     pub rend_type: i8,
     pub x1: f32,
     pub y1: f32,
     pub x2: f32,
     pub y2: f32,
     pub alpha: f32,
+    pub color: [u8; 3],
 }
 
 #[repr(C, packed)]
-pub struct RendNote {
+pub(crate) struct RendNote {
     pub rend_type: i8,
     pub note_type: i8,
     pub x: f32,
@@ -20,7 +176,7 @@ pub struct RendNote {
 }
 
 #[repr(C, packed)]
-pub struct RendClickEffect {
+pub(crate) struct RendClickEffect {
     pub rend_type: i8,
     pub x: f32,
     pub y: f32,
@@ -29,23 +185,27 @@ pub struct RendClickEffect {
 }
 
 #[repr(C, packed)]
-pub struct RendPoint {
+pub(crate) struct RendPoint {
     pub rend_type: i8,
     pub x: f32,
     pub y: f32,
 }
 
 #[repr(C, packed)]
-pub struct RendStatistics {
+pub(crate) struct RendStatistics {
     pub rend_type: i8,
     pub combo: u32,
     pub max_combo: u32,
     pub score: f32,
     pub accurate: f32,
+    pub rank: u8,
+    pub combo_status: u8,
+    pub failed: u8,
+    pub failed_at: f32,
 }
 
 #[repr(C, packed)]
-pub struct RendSplashEffect {
+pub(crate) struct RendSplashEffect {
     pub rend_type: i8,
     pub x: f32,
     pub y: f32,
@@ -54,14 +214,14 @@ pub struct RendSplashEffect {
 }
 
 #[repr(C, packed)]
-pub struct RendSound {
+pub(crate) struct RendSound {
     pub rend_type: i8,
     pub tap_sound: i8,
     pub drag_sound: i8,
     pub flick_sound: i8,
 }
 
-pub trait Dense {
+pub(crate) trait Dense {
     fn to_bytes(&self) -> &[u8]
     where
         Self: Sized,
@@ -75,6 +235,7 @@ pub trait Dense {
     }
 }
 
+impl Dense for RendFrameHeader {}
 impl Dense for RendLine {}
 impl Dense for RendNote {}
 impl Dense for RendClickEffect {}
@@ -82,3 +243,311 @@ impl Dense for RendPoint {}
 impl Dense for RendStatistics {}
 impl Dense for RendSplashEffect {}
 impl Dense for RendSound {}
+
+/// The header of a decoded frame
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct FrameHeader {
+    /// The protocol version of the frame
+    pub version: u16,
+
+    /// Whether the fields of the frame are big endian
+    pub big_endian: bool,
+
+    /// The time of the last tick, in seconds
+    pub frame_time: f64,
+
+    /// The number of records of type `i + 1`
+    pub counts: [u32; RECORD_TYPE_COUNT],
+}
+
+/// A decoded record of the render stream
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RenderCommand {
+    /// The header of the frame, always the first command
+    Header(FrameHeader),
+
+    /// A judge line between two points, type 1
+    Line {
+        /// The x of the first point
+        x1: f32,
+        /// The y of the first point
+        y1: f32,
+        /// The x of the second point
+        x2: f32,
+        /// The y of the second point
+        y2: f32,
+        /// The alpha of the line
+        alpha: f32,
+        /// The colour of the line as RGB
+        color: [u8; 3],
+    },
+
+    /// A note, or a part of a hold note, type 2
+    Note {
+        /// 1 to 4 for tap, drag, hold and flick, 5 to 7 for the head, body and
+        /// end of a hold note
+        note_type: i8,
+        /// The x of the center
+        x: f32,
+        /// The y of the center
+        y: f32,
+        /// The rotation in degrees
+        rotate: f32,
+        /// The height of a hold body
+        height: f32,
+        /// 1 if the note is highlighted
+        high_light: i8,
+    },
+
+    /// A click effect, type 3
+    ClickEffect {
+        /// The x of the center
+        x: f32,
+        /// The y of the center
+        y: f32,
+        /// The frame of the animation, 0 to 29
+        frame: i8,
+        /// 0 for perfect, 1 for good
+        tint_type: i8,
+    },
+
+    /// A touch point, type 4
+    Point {
+        /// The x of the touch
+        x: f32,
+        /// The y of the touch
+        y: f32,
+    },
+
+    /// The statistics of the play, type 5
+    Statistics {
+        /// The current combo
+        combo: u32,
+        /// The max combo so far
+        max_combo: u32,
+        /// The score
+        score: f32,
+        /// The accuracy, from 0 to 1
+        accurate: f32,
+        /// The rank of the score, 0 to 6 for φ, V, S, A, B, C and F
+        rank: u8,
+        /// 0 for all perfect, 1 for full combo, 2 for neither
+        combo_status: u8,
+        /// 1 if the run failed, 0 otherwise
+        failed: u8,
+        /// The time the run failed in seconds, 0 if it did not
+        failed_at: f32,
+    },
+
+    /// A splash particle, type 6
+    SplashEffect {
+        /// The x of the particle
+        x: f32,
+        /// The y of the particle
+        y: f32,
+        /// The frame of the animation, 0 to 29
+        frame: i8,
+        /// 0 for perfect, 1 for good
+        tint_type: i8,
+    },
+
+    /// The sounds to play in this frame, type 7
+    Sound {
+        /// The number of tap sounds
+        tap_sound: i8,
+        /// The number of drag sounds
+        drag_sound: i8,
+        /// The number of flick sounds
+        flick_sound: i8,
+    },
+}
+
+/// The error returned when the render stream can not be decoded
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DecodeError {
+    /// The stream does not start with `RENDER_MAGIC`
+    BadMagic,
+
+    /// The frame was written by another protocol version
+    UnsupportedVersion(u16),
+
+    /// The stream ends inside a record, or before the terminator
+    Truncated {
+        /// The byte offset of the record
+        offset: usize,
+    },
+
+    /// A record has an unknown type byte
+    UnknownRecord {
+        /// The byte offset of the record
+        offset: usize,
+        /// The type byte found
+        record_type: u8,
+    },
+}
+
+impl std::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecodeError::BadMagic => write!(f, "render stream does not start with the magic"),
+            DecodeError::UnsupportedVersion(version) => {
+                write!(f, "unsupported render protocol version: {version}")
+            }
+            DecodeError::Truncated { offset } => {
+                write!(f, "render stream is truncated at byte {offset}")
+            }
+            DecodeError::UnknownRecord {
+                offset,
+                record_type,
+            } => write!(f, "unknown record type {record_type} at byte {offset}"),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// Decode a frame written by `process_state_to_drawable`.
+///
+/// The header comes first, the iteration stops after the terminator or the
+/// first error.
+pub fn decode(bytes: &[u8]) -> impl Iterator<Item = Result<RenderCommand, DecodeError>> + '_ {
+    let mut reader = ByteReader::new(bytes);
+    let mut header_read = false;
+    let mut done = false;
+    std::iter::from_fn(move || {
+        if done {
+            return None;
+        }
+        let result = if header_read {
+            record(&mut reader)
+        } else {
+            header_read = true;
+            header(&mut reader).map(Some)
+        };
+        match result {
+            Ok(Some(command)) => Some(Ok(command)),
+            Ok(None) => {
+                done = true;
+                None
+            }
+            Err(err) => {
+                done = true;
+                Some(Err(err))
+            }
+        }
+    })
+}
+
+fn header(reader: &mut ByteReader) -> Result<RenderCommand, DecodeError> {
+    let truncated = DecodeError::Truncated { offset: 0 };
+    let magic = reader.bytes.get(..4).ok_or(truncated)?;
+    if magic != RENDER_MAGIC {
+        return Err(DecodeError::BadMagic);
+    }
+    reader.big_endian = *reader.bytes.get(6).ok_or(truncated)? == 1;
+    reader.offset = 4;
+    let version = reader.u16().ok_or(truncated)?;
+    if version != RENDER_PROTOCOL_VERSION {
+        return Err(DecodeError::UnsupportedVersion(version));
+    }
+    reader.offset += 1;
+    let frame_time = reader.f64().ok_or(truncated)?;
+    let mut counts = [0; RECORD_TYPE_COUNT];
+    for count in &mut counts {
+        *count = reader.u32().ok_or(truncated)?;
+    }
+    Ok(RenderCommand::Header(FrameHeader {
+        version,
+        big_endian: reader.big_endian,
+        frame_time,
+        counts,
+    }))
+}
+
+fn record(reader: &mut ByteReader) -> Result<Option<RenderCommand>, DecodeError> {
+    let offset = reader.offset;
+    let truncated = DecodeError::Truncated { offset };
+    let record_type = *reader.bytes.get(offset).ok_or(truncated)?;
+    reader.offset += 1;
+    let command = match record_type {
+        0 => return Ok(None),
+        1 => line(reader),
+        2 => note(reader),
+        3 => effect(reader).map(|(x, y, frame, tint_type)| RenderCommand::ClickEffect {
+            x,
+            y,
+            frame,
+            tint_type,
+        }),
+        4 => point(reader),
+        5 => statistics(reader),
+        6 => effect(reader).map(|(x, y, frame, tint_type)| RenderCommand::SplashEffect {
+            x,
+            y,
+            frame,
+            tint_type,
+        }),
+        7 => sound(reader),
+        _ => {
+            return Err(DecodeError::UnknownRecord {
+                offset,
+                record_type,
+            });
+        }
+    };
+    command.map(Some).ok_or(truncated)
+}
+
+fn line(reader: &mut ByteReader) -> Option<RenderCommand> {
+    Some(RenderCommand::Line {
+        x1: reader.f32()?,
+        y1: reader.f32()?,
+        x2: reader.f32()?,
+        y2: reader.f32()?,
+        alpha: reader.f32()?,
+        color: [reader.u8()?, reader.u8()?, reader.u8()?],
+    })
+}
+
+fn note(reader: &mut ByteReader) -> Option<RenderCommand> {
+    Some(RenderCommand::Note {
+        note_type: reader.i8()?,
+        x: reader.f32()?,
+        y: reader.f32()?,
+        rotate: reader.f32()?,
+        height: reader.f32()?,
+        high_light: reader.i8()?,
+    })
+}
+
+fn effect(reader: &mut ByteReader) -> Option<(f32, f32, i8, i8)> {
+    Some((reader.f32()?, reader.f32()?, reader.i8()?, reader.i8()?))
+}
+
+fn point(reader: &mut ByteReader) -> Option<RenderCommand> {
+    Some(RenderCommand::Point {
+        x: reader.f32()?,
+        y: reader.f32()?,
+    })
+}
+
+fn statistics(reader: &mut ByteReader) -> Option<RenderCommand> {
+    Some(RenderCommand::Statistics {
+        combo: reader.u32()?,
+        max_combo: reader.u32()?,
+        score: reader.f32()?,
+        accurate: reader.f32()?,
+        rank: reader.u8()?,
+        combo_status: reader.u8()?,
+        failed: reader.u8()?,
+        failed_at: reader.f32()?,
+    })
+}
+
+fn sound(reader: &mut ByteReader) -> Option<RenderCommand> {
+    Some(RenderCommand::Sound {
+        tap_sound: reader.i8()?,
+        drag_sound: reader.i8()?,
+        flick_sound: reader.i8()?,
+    })
+}
@@ -0,0 +1,557 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ENGINE, bytes::ByteReader, engine::Engine, judge_config::JudgeConfig, play_mode::PlayMode,
+    states::SeekPolicy, states_statistics::ComboOrder,
+};
+
+/// The magic at the start of an encoded replay
+pub const REPLAY_MAGIC: [u8; 4] = *b"PTDR";
+
+/// The version of the encoded replay format
+pub const REPLAY_FORMAT_VERSION: u16 = 3;
+
+/// An input of the engine, in the order it was received
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum ReplayEvent {
+    /// A call of `tick_all`
+    Tick {
+        /// The time of the tick in seconds
+        time: f64,
+        /// The time since the last tick in seconds
+        delta: f64,
+        /// Whether the notes are played automatically
+        auto: bool,
+    },
+
+    /// A call of `set_touch_down`
+    TouchDown {
+        /// The id of the touch
+        id: u8,
+        /// The x of the touch
+        x: f32,
+        /// The y of the touch
+        y: f32,
+    },
+
+    /// A call of `set_touch_move`
+    TouchMove {
+        /// The id of the touch
+        id: u8,
+        /// The x of the touch
+        x: f32,
+        /// The y of the touch
+        y: f32,
+    },
+
+    /// A call of `set_touch_up`
+    TouchUp {
+        /// The id of the touch
+        id: u8,
+    },
+
+    /// A call of `clear_touch`
+    ClearTouch,
+
+    /// A call of `seek` or `reset_note_state`
+    Seek {
+        /// The time to seek to in seconds
+        time: f64,
+        /// How the notes before the time are treated
+        policy: SeekPolicy,
+    },
+
+    /// A call of `set_judge_config`
+    SetJudgeConfig(JudgeConfig),
+
+    /// A call of `set_play_mode`
+    SetPlayMode(PlayMode),
+
+    /// A call of `set_combo_order`
+    SetComboOrder(ComboOrder),
+}
+
+/// Where the play stands, to tell whether a replay can be recorded from it
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) enum PlayOrigin {
+    /// The chart was loaded and has not been ticked
+    Loaded,
+
+    /// The play was moved by a seek and has not been ticked since
+    Seeked {
+        /// The time of the seek in seconds
+        time: f64,
+        /// How the notes before the time were treated
+        policy: SeekPolicy,
+    },
+
+    /// The play has been ticked, or restored from a snapshot
+    Ticked,
+}
+
+/// A recorded play of a chart.
+///
+/// The chart itself is not part of the replay, it must be loaded before the
+/// replay is played.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Replay {
+    judge_config: JudgeConfig,
+    play_mode: PlayMode,
+    combo_order: ComboOrder,
+    events: Vec<ReplayEvent>,
+}
+
+/// The error returned when an encoded replay can not be decoded
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ReplayError {
+    /// The replay does not start with `REPLAY_MAGIC`
+    BadMagic,
+
+    /// The replay was written by another format version
+    UnsupportedVersion(u16),
+
+    /// The replay ends inside an event
+    Truncated {
+        /// The byte offset of the event
+        offset: usize,
+    },
+
+    /// An event has an unknown type byte
+    UnknownEvent {
+        /// The byte offset of the event
+        offset: usize,
+        /// The type byte found
+        event_type: u8,
+    },
+
+    /// A play mode byte is unknown
+    UnknownPlayMode {
+        /// The byte offset of the play mode
+        offset: usize,
+        /// The play mode byte found
+        mode: u8,
+    },
+
+    /// A combo order byte is unknown
+    UnknownComboOrder {
+        /// The byte offset of the combo order
+        offset: usize,
+        /// The combo order byte found
+        order: u8,
+    },
+
+    /// A seek event has an unknown policy byte
+    UnknownSeekPolicy {
+        /// The byte offset of the event
+        offset: usize,
+        /// The policy byte found
+        policy: u8,
+    },
+}
+
+impl std::fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReplayError::BadMagic => write!(f, "replay does not start with the magic"),
+            ReplayError::UnsupportedVersion(version) => {
+                write!(f, "unsupported replay format version: {version}")
+            }
+            ReplayError::Truncated { offset } => {
+                write!(f, "replay is truncated at byte {offset}")
+            }
+            ReplayError::UnknownPlayMode { offset, mode } => {
+                write!(f, "unknown play mode {mode} at byte {offset}")
+            }
+            ReplayError::UnknownComboOrder { offset, order } => {
+                write!(f, "unknown combo order {order} at byte {offset}")
+            }
+            ReplayError::UnknownEvent { offset, event_type } => {
+                write!(f, "unknown event type {event_type} at byte {offset}")
+            }
+            ReplayError::UnknownSeekPolicy { offset, policy } => {
+                write!(f, "unknown seek policy {policy} at byte {offset}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+impl Replay {
+    /// The timing windows the replay was recorded with
+    #[must_use]
+    pub fn judge_config(&self) -> JudgeConfig {
+        self.judge_config
+    }
+
+    /// The play mode the replay was recorded with
+    #[must_use]
+    pub fn play_mode(&self) -> PlayMode {
+        self.play_mode
+    }
+
+    /// The combo order the replay was recorded with
+    #[must_use]
+    pub fn combo_order(&self) -> ComboOrder {
+        self.combo_order
+    }
+
+    /// The recorded inputs, in order
+    #[must_use]
+    pub fn events(&self) -> &[ReplayEvent] {
+        &self.events
+    }
+
+    /// Encode the replay into the compact binary format.
+    ///
+    /// The replay starts with `REPLAY_MAGIC`, the format version as `u16`, the
+    /// judge config as six `f64`, early before late and perfect before bad,
+    /// the play mode as `u8` (0 normal, 1 sudden death, 2 perfect only, 3 no
+    /// fail) and the combo order as `u8` (0 judgment, 1 end time). Every event
+    /// follows as a type byte and its fields, all little endian:
+    ///
+    /// | type | event            | fields                            |
+    /// |------|------------------|-----------------------------------|
+    /// | 1    | `Tick`           | time f64, delta f64, auto u8      |
+    /// | 2    | `TouchDown`      | id u8, x f32, y f32               |
+    /// | 3    | `TouchMove`      | id u8, x f32, y f32               |
+    /// | 4    | `TouchUp`        | id u8                             |
+    /// | 5    | `ClearTouch`     |                                   |
+    /// | 6    | `Seek`           | time f64, policy u8 (0, 1 or 2)   |
+    /// | 7    | `SetJudgeConfig` | six f64, as in the header         |
+    /// | 8    | `SetPlayMode`    | mode u8, as in the header         |
+    /// | 9    | `SetComboOrder`  | order u8, as in the header        |
+    #[must_use]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(56 + self.events.len() * 18);
+        bytes.extend_from_slice(&REPLAY_MAGIC);
+        bytes.extend_from_slice(&REPLAY_FORMAT_VERSION.to_le_bytes());
+        write_judge_config(&mut bytes, self.judge_config);
+        bytes.push(play_mode_to_byte(self.play_mode));
+        bytes.push(combo_order_to_byte(self.combo_order));
+        for event in &self.events {
+            match *event {
+                ReplayEvent::Tick { time, delta, auto } => {
+                    bytes.push(1);
+                    bytes.extend_from_slice(&time.to_le_bytes());
+                    bytes.extend_from_slice(&delta.to_le_bytes());
+                    bytes.push(u8::from(auto));
+                }
+                ReplayEvent::TouchDown { id, x, y } | ReplayEvent::TouchMove { id, x, y } => {
+                    bytes.push(if matches!(event, ReplayEvent::TouchDown { .. }) {
+                        2
+                    } else {
+                        3
+                    });
+                    bytes.push(id);
+                    bytes.extend_from_slice(&x.to_le_bytes());
+                    bytes.extend_from_slice(&y.to_le_bytes());
+                }
+                ReplayEvent::TouchUp { id } => {
+                    bytes.push(4);
+                    bytes.push(id);
+                }
+                ReplayEvent::ClearTouch => bytes.push(5),
+                ReplayEvent::Seek { time, policy } => {
+                    bytes.push(6);
+                    bytes.extend_from_slice(&time.to_le_bytes());
+                    bytes.push(match policy {
+                        SeekPolicy::Practice => 0,
+                        SeekPolicy::AutoplayFill => 1,
+                        SeekPolicy::Reset => 2,
+                    });
+                }
+                ReplayEvent::SetJudgeConfig(config) => {
+                    bytes.push(7);
+                    write_judge_config(&mut bytes, config);
+                }
+                ReplayEvent::SetPlayMode(mode) => {
+                    bytes.push(8);
+                    bytes.push(play_mode_to_byte(mode));
+                }
+                ReplayEvent::SetComboOrder(order) => {
+                    bytes.push(9);
+                    bytes.push(combo_order_to_byte(order));
+                }
+            }
+        }
+        bytes
+    }
+
+    /// Decode a replay written by `Replay::to_bytes`
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the bytes are not a replay of a
+    /// supported version.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Replay, ReplayError> {
+        let mut reader = ByteReader::new(bytes);
+        let truncated = ReplayError::Truncated { offset: 0 };
+        if *bytes.get(..4).ok_or(truncated)? != REPLAY_MAGIC {
+            return Err(ReplayError::BadMagic);
+        }
+        reader.offset = 4;
+        let version = reader.u16().ok_or(truncated)?;
+        if version != REPLAY_FORMAT_VERSION {
+            return Err(ReplayError::UnsupportedVersion(version));
+        }
+        let judge_config = read_judge_config(&mut reader).ok_or(truncated)?;
+        let play_mode = read_play_mode(&mut reader)?.ok_or(truncated)?;
+        let combo_order = read_combo_order(&mut reader)?.ok_or(truncated)?;
+        let mut events = Vec::new();
+        while !reader.is_finished() {
+            events.push(event(&mut reader)?);
+        }
+        Ok(Replay {
+            judge_config,
+            play_mode,
+            combo_order,
+            events,
+        })
+    }
+}
+
+fn event(reader: &mut ByteReader) -> Result<ReplayEvent, ReplayError> {
+    let offset = reader.offset;
+    let truncated = ReplayError::Truncated { offset };
+    let event_type = reader.u8().ok_or(truncated)?;
+    let event = match event_type {
+        1 => (|| {
+            Some(ReplayEvent::Tick {
+                time: reader.f64()?,
+                delta: reader.f64()?,
+                auto: reader.u8()? != 0,
+            })
+        })(),
+        2 => (|| {
+            Some(ReplayEvent::TouchDown {
+                id: reader.u8()?,
+                x: reader.f32()?,
+                y: reader.f32()?,
+            })
+        })(),
+        3 => (|| {
+            Some(ReplayEvent::TouchMove {
+                id: reader.u8()?,
+                x: reader.f32()?,
+                y: reader.f32()?,
+            })
+        })(),
+        4 => reader.u8().map(|id| ReplayEvent::TouchUp { id }),
+        5 => Some(ReplayEvent::ClearTouch),
+        6 => {
+            let (time, policy) = (|| Some((reader.f64()?, reader.u8()?)))().ok_or(truncated)?;
+            let policy = match policy {
+                0 => SeekPolicy::Practice,
+                1 => SeekPolicy::AutoplayFill,
+                2 => SeekPolicy::Reset,
+                _ => return Err(ReplayError::UnknownSeekPolicy { offset, policy }),
+            };
+            Some(ReplayEvent::Seek { time, policy })
+        }
+        7 => read_judge_config(reader).map(ReplayEvent::SetJudgeConfig),
+        8 => read_play_mode(reader)?.map(ReplayEvent::SetPlayMode),
+        9 => read_combo_order(reader)?.map(ReplayEvent::SetComboOrder),
+        _ => return Err(ReplayError::UnknownEvent { offset, event_type }),
+    };
+    event.ok_or(truncated)
+}
+
+fn write_judge_config(bytes: &mut Vec<u8>, config: JudgeConfig) {
+    let JudgeConfig { perfect, good, bad } = config;
+    for it in [
+        perfect.early,
+        good.early,
+        bad.early,
+        perfect.late,
+        good.late,
+        bad.late,
+    ] {
+        bytes.extend_from_slice(&it.to_le_bytes());
+    }
+}
+
+fn read_judge_config(reader: &mut ByteReader) -> Option<JudgeConfig> {
+    let mut windows = [0.0; 6];
+    for it in &mut windows {
+        *it = reader.f64()?;
+    }
+    Some(JudgeConfig::asymmetric(
+        (windows[0], windows[1], windows[2]),
+        (windows[3], windows[4], windows[5]),
+    ))
+}
+
+fn play_mode_to_byte(mode: PlayMode) -> u8 {
+    match mode {
+        PlayMode::Normal => 0,
+        PlayMode::SuddenDeath => 1,
+        PlayMode::PerfectOnly => 2,
+        PlayMode::NoFail => 3,
+    }
+}
+
+/// Read a play mode, none if the replay ends before it
+fn read_play_mode(reader: &mut ByteReader) -> Result<Option<PlayMode>, ReplayError> {
+    let offset = reader.offset;
+    let Some(mode) = reader.u8() else {
+        return Ok(None);
+    };
+    Ok(Some(match mode {
+        0 => PlayMode::Normal,
+        1 => PlayMode::SuddenDeath,
+        2 => PlayMode::PerfectOnly,
+        3 => PlayMode::NoFail,
+        _ => return Err(ReplayError::UnknownPlayMode { offset, mode }),
+    }))
+}
+
+fn combo_order_to_byte(order: ComboOrder) -> u8 {
+    match order {
+        ComboOrder::Judgment => 0,
+        ComboOrder::EndTime => 1,
+    }
+}
+
+/// Read a combo order, none if the replay ends before it
+fn read_combo_order(reader: &mut ByteReader) -> Result<Option<ComboOrder>, ReplayError> {
+    let offset = reader.offset;
+    let Some(order) = reader.u8() else {
+        return Ok(None);
+    };
+    Ok(Some(match order {
+        0 => ComboOrder::Judgment,
+        1 => ComboOrder::EndTime,
+        _ => return Err(ReplayError::UnknownComboOrder { offset, order }),
+    }))
+}
+
+/// Feeds a replay back into an engine, one tick at a time
+pub struct ReplayPlayer {
+    replay: Replay,
+    cursor: usize,
+}
+
+impl ReplayPlayer {
+    /// Create a player at the start of the replay
+    #[must_use]
+    pub fn new(replay: Replay) -> ReplayPlayer {
+        ReplayPlayer { replay, cursor: 0 }
+    }
+
+    /// Whether every event has been fed
+    #[must_use]
+    pub fn is_finished(&self) -> bool {
+        self.cursor >= self.replay.events.len()
+    }
+
+    /// Feed the events up to and including the next tick into `engine`.
+    ///
+    /// On the first step, the judge config, the play mode and the combo order
+    /// of the replay are applied and the notes are reset. Returns false if
+    /// the replay is already finished.
+    pub fn step(&mut self, engine: &mut Engine) -> bool {
+        if self.is_finished() {
+            return false;
+        }
+        if self.cursor == 0 {
+            engine.set_judge_config(self.replay.judge_config);
+            engine.set_play_mode(self.replay.play_mode);
+            engine.set_combo_order(self.replay.combo_order);
+            engine.seek(0.0, SeekPolicy::Reset);
+        }
+        while let Some(event) = self.replay.events.get(self.cursor) {
+            self.cursor += 1;
+            engine.apply_replay_event(event);
+            if matches!(event, ReplayEvent::Tick { .. }) {
+                break;
+            }
+        }
+        true
+    }
+}
+
+impl Engine {
+    /// Start recording the inputs and settings into a new replay, dropping
+    /// the replay being recorded if any.
+    ///
+    /// Recording can only start before the first tick after the chart is
+    /// loaded or after a seek, the seek is the first event of the replay.
+    /// Returns false without recording once the play has been ticked, or
+    /// while a touch is down. Loading another chart, restoring a snapshot or
+    /// clearing the engine stops the recording.
+    pub fn start_recording(&mut self) -> bool {
+        if self.touch_states.iter().any(|it| it.enable) {
+            return false;
+        }
+        let events = match self.play_origin {
+            PlayOrigin::Loaded => Vec::new(),
+            PlayOrigin::Seeked { time, policy } => vec![ReplayEvent::Seek { time, policy }],
+            PlayOrigin::Ticked => return false,
+        };
+        self.replay_recorder = Some(Replay {
+            judge_config: self.judge_config,
+            play_mode: self.play_mode,
+            combo_order: self.combo_order,
+            events,
+        });
+        true
+    }
+
+    /// Stop recording and take the recorded replay, if recording
+    pub fn stop_recording(&mut self) -> Option<Replay> {
+        self.replay_recorder.take()
+    }
+
+    /// Whether the inputs are being recorded
+    #[must_use]
+    pub fn is_recording(&self) -> bool {
+        self.replay_recorder.is_some()
+    }
+
+    /// Play a whole replay against the loaded chart.
+    ///
+    /// The settings of the replay are applied and the notes are reset first,
+    /// so the judgments and statistics are identical to the recorded play.
+    pub fn play_replay(&mut self, replay: &Replay) {
+        let mut player = ReplayPlayer::new(replay.clone());
+        while player.step(self) {}
+    }
+
+    /// Apply a single recorded input
+    pub fn apply_replay_event(&mut self, event: &ReplayEvent) {
+        match *event {
+            ReplayEvent::Tick { time, delta, auto } => self.tick_all(time, delta, auto),
+            ReplayEvent::TouchDown { id, x, y } => self.set_touch_down(id.into(), x, y),
+            ReplayEvent::TouchMove { id, x, y } => self.set_touch_move(id.into(), x, y),
+            ReplayEvent::TouchUp { id } => self.set_touch_up(id.into()),
+            ReplayEvent::ClearTouch => self.clear_touch(),
+            ReplayEvent::Seek { time, policy } => self.seek(time, policy),
+            ReplayEvent::SetJudgeConfig(config) => self.set_judge_config(config),
+            ReplayEvent::SetPlayMode(mode) => self.set_play_mode(mode),
+            ReplayEvent::SetComboOrder(order) => self.set_combo_order(order),
+        }
+    }
+
+    pub(crate) fn record(&mut self, event: ReplayEvent) {
+        if let Some(replay) = &mut self.replay_recorder {
+            replay.events.push(event);
+        }
+    }
+}
+
+/// Start recording the inputs, see `Engine::start_recording`
+pub fn start_recording() -> bool {
+    ENGINE.with_borrow_mut(Engine::start_recording)
+}
+
+/// Stop recording and take the recorded replay, if recording
+#[must_use]
+pub fn stop_recording() -> Option<Replay> {
+    ENGINE.with_borrow_mut(Engine::stop_recording)
+}
+
+/// Play a whole replay against the loaded chart, see `Engine::play_replay`
+pub fn play_replay(replay: &Replay) {
+    ENGINE.with_borrow_mut(|it| it.play_replay(replay));
+}
@@ -0,0 +1,260 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::{
+    chart::{ChartRaw, NoteType},
+    engine::Engine,
+    judge_config::JudgeConfig,
+    play_mode::PlayMode,
+    replay::{Replay, ReplayEvent, ReplayPlayer},
+    states::{self, Metadata, NoteScore},
+    states_judge::JudgeEventKind,
+    states_statistics::ChartStatistics,
+};
+
+/// How long the autoplay keeps ticking after the estimated length, so the
+/// last notes are judged
+const AUTOPLAY_TAIL_IN_SECOND: f64 = 1.0;
+
+/// Where the inputs of a simulation come from
+#[derive(Clone, Debug)]
+pub enum SimulationInput {
+    /// Every note is played automatically
+    Autoplay,
+
+    /// The ticks, touches and settings of a recorded play
+    Replay(Replay),
+}
+
+/// The options of `simulate`
+#[derive(Clone, Debug)]
+pub struct SimulationOptions {
+    /// The number of ticks per second when playing automatically, 60 by
+    /// default. A replay brings its own ticks.
+    pub tick_rate: f64,
+
+    /// Where the inputs come from
+    pub input: SimulationInput,
+
+    /// The timing windows used to judge notes when playing automatically. A
+    /// replay uses the config it was recorded with.
+    pub judge_config: JudgeConfig,
+
+    /// The rules that decide when the run fails when playing automatically. A
+    /// replay uses the mode it was recorded with.
+    pub play_mode: PlayMode,
+}
+
+/// The error returned when a simulation can not be run
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SimulationError {
+    /// The tick rate is not a positive finite number
+    InvalidTickRate(f64),
+}
+
+impl std::fmt::Display for SimulationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SimulationError::InvalidTickRate(rate) => {
+                write!(f, "tick rate must be positive and finite: {rate}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SimulationError {}
+
+impl Default for SimulationOptions {
+    fn default() -> Self {
+        SimulationOptions {
+            tick_rate: 60.0,
+            input: SimulationInput::Autoplay,
+            judge_config: JudgeConfig::default(),
+            play_mode: PlayMode::default(),
+        }
+    }
+}
+
+/// The final judgement of a note
+#[derive(Serialize, Clone, Copy, Debug)]
+pub struct NoteJudgment {
+    /// The index of the judge line
+    pub line: usize,
+
+    /// Whether the note is above the line
+    pub above: bool,
+
+    /// The index of the note on its side of the line, ordered by time
+    pub note_index: usize,
+
+    /// The type of the note
+    pub note_type: NoteType,
+
+    /// The time of the note in seconds
+    pub time: f64,
+
+    /// The grade of the note
+    pub grade: NoteScore,
+
+    /// The signed offset of the hit in milliseconds, negative when early.
+    /// Drag and flick notes have none.
+    pub offset_ms: Option<f64>,
+
+    /// The time the grade was settled in seconds, none if it never was
+    pub judged_at: Option<f64>,
+}
+
+/// Something unexpected found by a simulation
+#[derive(Serialize, Clone, Copy, Debug)]
+pub enum SimulationAnomaly {
+    /// The note was not judged by the end of the simulation
+    NeverJudged {
+        /// The index of the judge line
+        line: usize,
+        /// Whether the note is above the line
+        above: bool,
+        /// The index of the note on its side of the line
+        note_index: usize,
+    },
+
+    /// The note was not judged as perfect by autoplay
+    AutoplayNotPerfect {
+        /// The index of the judge line
+        line: usize,
+        /// Whether the note is above the line
+        above: bool,
+        /// The index of the note on its side of the line
+        note_index: usize,
+        /// The grade of the note
+        grade: NoteScore,
+    },
+}
+
+/// The result of `simulate`
+#[derive(Serialize)]
+pub struct SimulationReport {
+    /// The metadata of the loaded chart
+    pub metadata: Metadata,
+
+    /// The statistics at the end of the simulation
+    pub statistics: ChartStatistics,
+
+    /// The judgement of every note, ordered by the time the notes end
+    pub notes: Vec<NoteJudgment>,
+
+    /// The problems found, empty for a clean run
+    pub anomalies: Vec<SimulationAnomaly>,
+
+    /// The number of ticks simulated
+    pub tick_count: usize,
+}
+
+/// Play a whole chart without rendering, on an engine of its own.
+///
+/// The chart is loaded without rejecting validation errors, the diagnostics
+/// are in the metadata of the report. A replay is played like
+/// `Engine::play_replay` does.
+///
+/// # Errors
+///
+/// This function will return an error if the tick rate of an autoplay is not
+/// a positive finite number.
+pub fn simulate(
+    chart: ChartRaw,
+    options: &SimulationOptions,
+) -> Result<SimulationReport, SimulationError> {
+    if matches!(options.input, SimulationInput::Autoplay)
+        && !(options.tick_rate.is_finite() && options.tick_rate > 0.0)
+    {
+        return Err(SimulationError::InvalidTickRate(options.tick_rate));
+    }
+    let mut engine = Engine::new();
+    engine.set_collect_judge_events(true);
+    engine.set_judge_config(options.judge_config);
+    engine.set_play_mode(options.play_mode);
+    let metadata = engine.init(chart);
+    let mut judged_at = HashMap::new();
+    let mut collect = |engine: &mut Engine| {
+        for event in engine.drain_judge_events() {
+            if matches!(
+                event.kind,
+                JudgeEventKind::Hit | JudgeEventKind::Miss | JudgeEventKind::HoldBreak
+            ) {
+                judged_at.insert((event.line, event.above, event.note_index), event.time);
+            }
+        }
+    };
+    let tick_count = match &options.input {
+        SimulationInput::Autoplay => {
+            let delta = 1.0 / options.tick_rate;
+            let end = metadata.length_in_second + AUTOPLAY_TAIL_IN_SECOND;
+            let mut tick = 0u32;
+            let mut time = 0.0;
+            while time <= end {
+                engine.tick_all(time, delta, true);
+                collect(&mut engine);
+                tick += 1;
+                time = f64::from(tick) * delta;
+            }
+            tick as usize
+        }
+        SimulationInput::Replay(replay) => {
+            let mut player = ReplayPlayer::new(replay.clone());
+            while player.step(&mut engine) {
+                collect(&mut engine);
+            }
+            replay
+                .events()
+                .iter()
+                .filter(|it| matches!(it, ReplayEvent::Tick { .. }))
+                .count()
+        }
+    };
+    let mut notes = Vec::new();
+    let mut anomalies = Vec::new();
+    for index in &engine.flatten_note_index {
+        let Some(state) = index.index(&engine.line_states) else {
+            continue;
+        };
+        let key = (index.index_in_line, index.above, index.index_in_notes);
+        let (line, above, note_index) = key;
+        let seconds_per_tick = states::get_seconds_per_tick(engine.line_states[line].bpm);
+        let grade = state.score;
+        if grade == NoteScore::None {
+            anomalies.push(SimulationAnomaly::NeverJudged {
+                line,
+                above,
+                note_index,
+            });
+        } else if matches!(options.input, SimulationInput::Autoplay) && grade != NoteScore::Perfect
+        {
+            anomalies.push(SimulationAnomaly::AutoplayNotPerfect {
+                line,
+                above,
+                note_index,
+                grade,
+            });
+        }
+        notes.push(NoteJudgment {
+            line,
+            above,
+            note_index,
+            note_type: state.note.r#type,
+            time: f64::from(state.note.time) * seconds_per_tick,
+            grade,
+            offset_ms: match grade {
+                NoteScore::Perfect | NoteScore::Good | NoteScore::Bad => state.hit_offset,
+                _ => None,
+            },
+            judged_at: judged_at.get(&key).copied(),
+        });
+    }
+    Ok(SimulationReport {
+        metadata,
+        statistics: engine.chart_statistics.clone(),
+        notes,
+        anomalies,
+        tick_count,
+    })
+}
@@ -0,0 +1,104 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ENGINE,
+    engine::Engine,
+    input::TouchInfo,
+    replay::PlayOrigin,
+    states::LineState,
+    states_effect::{HitEffect, SoundEffect, SplashEffect},
+    states_judge::JudgeEvent,
+    states_statistics::{self, ChartStatistics},
+};
+
+/// A copy of the complete state of a play, including the chart, the judged
+/// notes, touches, effects and statistics.
+///
+/// Settings such as the judge config and the image offsets are not part of
+/// the snapshot.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct EngineSnapshot {
+    line_states: Vec<LineState>,
+    touch_states: Vec<TouchInfo>,
+    hit_effect_pool: Vec<HitEffect>,
+    splash_effect_pool: Vec<SplashEffect>,
+    chart_statistics: ChartStatistics,
+    sound_pool: SoundEffect,
+    frame_time: f64,
+    judge_events: Vec<JudgeEvent>,
+}
+
+impl EngineSnapshot {
+    /// The time of the last tick when the snapshot was taken
+    #[must_use]
+    pub fn frame_time(&self) -> f64 {
+        self.frame_time
+    }
+
+    /// Serialize the snapshot to json
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if a value can not be represented
+    /// in json.
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string(self)
+    }
+
+    /// Deserialize a snapshot from json
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the json is not a snapshot.
+    pub fn from_json(json: &str) -> Result<EngineSnapshot, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+}
+
+impl Engine {
+    /// Take a snapshot of the complete state of the play
+    #[must_use]
+    pub fn snapshot(&self) -> EngineSnapshot {
+        EngineSnapshot {
+            line_states: self.line_states.clone(),
+            touch_states: self.touch_states.to_vec(),
+            hit_effect_pool: self.hit_effect_pool.to_vec(),
+            splash_effect_pool: self.splash_effect_pool.to_vec(),
+            chart_statistics: self.chart_statistics.clone(),
+            sound_pool: self.sound_pool.clone(),
+            frame_time: self.frame_time,
+            judge_events: self.judge_events.clone(),
+        }
+    }
+
+    /// Restore the state of the play from a snapshot
+    pub fn restore(&mut self, snapshot: &EngineSnapshot) {
+        fn copy_pool<T: Clone + Default>(pool: &mut [T], saved: &[T]) {
+            for (i, it) in pool.iter_mut().enumerate() {
+                *it = saved.get(i).cloned().unwrap_or_default();
+            }
+        }
+        self.line_states.clone_from(&snapshot.line_states);
+        copy_pool(&mut self.touch_states, &snapshot.touch_states);
+        copy_pool(&mut self.hit_effect_pool, &snapshot.hit_effect_pool);
+        copy_pool(&mut self.splash_effect_pool, &snapshot.splash_effect_pool);
+        self.chart_statistics = snapshot.chart_statistics.clone();
+        self.sound_pool = snapshot.sound_pool.clone();
+        self.frame_time = snapshot.frame_time;
+        self.judge_events.clone_from(&snapshot.judge_events);
+        self.replay_recorder = None;
+        self.play_origin = PlayOrigin::Ticked;
+        states_statistics::init_flatten_line_state(self);
+    }
+}
+
+/// Take a snapshot of the complete state of the play
+#[must_use]
+pub fn snapshot() -> EngineSnapshot {
+    ENGINE.with_borrow(Engine::snapshot)
+}
+
+/// Restore the state of the play from a snapshot
+pub fn restore(snapshot: &EngineSnapshot) {
+    ENGINE.with_borrow_mut(|it| it.restore(snapshot));
+}
@@ -1,11 +1,16 @@
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    LINE_STATES,
+    ENGINE,
     chart::{self},
-    states_effect, states_judge, states_lines, states_statistics,
+    chart_validation,
+    engine::Engine,
+    replay::{PlayOrigin, ReplayEvent},
+    states_effect::{self, HitEffect, SplashEffect},
+    states_judge, states_lines, states_statistics,
 };
 
+#[derive(Serialize, Deserialize, Clone)]
 pub struct LineState {
     pub enable: bool,
     pub x: f64,
@@ -26,23 +31,64 @@ pub struct LineState {
     pub rotate_events: Vec<chart::Event2>,
     pub alpha_events: Vec<chart::Event2>,
     pub bpm: f64,
+    /// The colour of the line, replacing the tint of the combo status
+    pub color: Option<[u8; 3]>,
 }
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+/// The grade of a note
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
 pub enum NoteScore {
+    /// A perfect hit
     Perfect,
+
+    /// A good hit
     Good,
+
+    /// A bad hit
     Bad,
+
+    /// The note was missed
     Miss,
+
+    /// The note is not judged yet
     None,
+
+    /// The note was skipped by seeking and does not count in the statistics
+    Excluded,
+}
+
+/// How the notes before the target time are treated when seeking
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SeekPolicy {
+    /// The skipped notes are excluded from the statistics, for practicing a
+    /// part of the chart
+    Practice,
+
+    /// The skipped notes are judged as perfect
+    AutoplayFill,
+
+    /// Every note is unjudged, as if the chart was just loaded, for
+    /// restarting the play.
+    ///
+    /// Seeking forward with this policy leaves the skipped notes to be missed
+    /// on the next tick. That breaks the combo, and fails the run in a
+    /// `PlayMode` that fails on a miss. Use `Practice` or `AutoplayFill` to
+    /// skip notes.
+    Reset,
 }
 
+#[derive(Serialize, Deserialize, Clone)]
 pub struct NoteState {
     pub note: chart::Note,
     pub highlight: bool,
     pub score: NoteScore,
     pub hold_cool_down: f64,
     pub extra_score: NoteScore,
+    /// The signed offset of the hit in milliseconds, negative when early.
+    /// Drag and flick notes have none.
+    pub hit_offset: Option<f64>,
+    /// The order in which the note was judged by ticking, counting from 0
+    pub judge_sequence: Option<u32>,
 }
 
 /// Metadata of the level
@@ -54,8 +100,18 @@ pub struct Metadata {
     /// The offset of the chart
     pub offset: f64,
 
-    /// The format version of the chart
+    /// The format version of the chart, the `RPEVersion` of an RPE chart, or 0
+    /// for a PEC chart
     pub format_version: i32,
+
+    /// The features lost when converting the chart to the standard format
+    pub conversion_report: chart::ConversionReport,
+
+    /// The problems found by validating the chart
+    pub diagnostics: Vec<chart_validation::Diagnostic>,
+
+    /// How far the stored floor positions were off, if they were recomputed
+    pub floor_position_report: Option<chart_validation::FloorPositionReport>,
 }
 
 impl Default for LineState {
@@ -80,6 +136,7 @@ impl Default for LineState {
             alpha_events: vec![],
             rotate_events: vec![],
             bpm: 0.0,
+            color: None,
         }
     }
 }
@@ -91,6 +148,8 @@ impl Default for NoteState {
             score: NoteScore::None,
             hold_cool_down: 0.0,
             extra_score: NoteScore::None,
+            hit_offset: None,
+            judge_sequence: None,
             note: chart::Note {
                 r#type: chart::NoteType::Tap,
                 time: 0,
@@ -107,40 +166,116 @@ pub fn get_seconds_per_tick(bpm: f64) -> f64 {
     60.0 / bpm / 32.0
 }
 
-/// Reset the state of notes that before the `before_time_in_second` to PERFECT
-pub fn reset_note_state(before_time_in_second: f64) {
-    LINE_STATES.with_borrow_mut(|state| {
-        for line in state.iter_mut() {
+impl Engine {
+    /// Reset the state of notes that before the `before_time_in_second` to PERFECT
+    pub fn reset_note_state(&mut self, before_time_in_second: f64) {
+        self.seek(before_time_in_second, SeekPolicy::AutoplayFill);
+    }
+
+    /// Move the play to `time_in_second`.
+    ///
+    /// The notes are judged again according to `policy`. Effects, sounds,
+    /// touches, pending judge events and the event caches of the lines are
+    /// reset, and the lines are ticked to the new time.
+    pub fn seek(&mut self, time_in_second: f64, policy: SeekPolicy) {
+        self.record(ReplayEvent::Seek {
+            time: time_in_second,
+            policy,
+        });
+        self.play_origin = PlayOrigin::Seeked {
+            time: time_in_second,
+            policy,
+        };
+        for line in &mut self.line_states {
+            line.event_speed_index_cache = 0;
+            line.event_move_index_cache = 0;
+            line.event_rotate_index_cache = 0;
+            line.event_alpha_index_cache = 0;
             let seconds_per_tick = get_seconds_per_tick(line.bpm);
+            let skipped = match policy {
+                SeekPolicy::Practice => NoteScore::Excluded,
+                SeekPolicy::AutoplayFill => NoteScore::Perfect,
+                SeekPolicy::Reset => NoteScore::None,
+            };
             let process_notes = |notes: &mut [NoteState]| {
                 for note in notes.iter_mut() {
                     note.hold_cool_down = 0.0;
+                    note.hit_offset = None;
+                    note.judge_sequence = None;
                     let note_time_in_second = f64::from(note.note.time) * seconds_per_tick;
                     let hold_time_in_second =
                         (f64::from(note.note.time) + note.note.hold_time) * seconds_per_tick;
-                    if note_time_in_second >= before_time_in_second {
+                    if policy == SeekPolicy::Reset || note_time_in_second >= time_in_second {
                         note.extra_score = NoteScore::None;
                         note.score = NoteScore::None;
-                    } else if hold_time_in_second >= before_time_in_second {
-                        note.score = NoteScore::None;
+                    } else if hold_time_in_second >= time_in_second {
+                        note.score = if policy == SeekPolicy::Practice {
+                            NoteScore::Excluded
+                        } else {
+                            NoteScore::None
+                        };
+                        note.extra_score = skipped;
                     } else {
-                        note.score = NoteScore::Perfect;
-                        note.extra_score = NoteScore::Perfect;
+                        note.score = skipped;
+                        note.extra_score = skipped;
                     }
                 }
             };
             process_notes(&mut line.notes_above_state);
             process_notes(&mut line.notes_below_state);
         }
-    });
-    states_statistics::refresh_chart_statistics();
+        self.hit_effect_pool = std::array::from_fn(|_| HitEffect::default());
+        self.splash_effect_pool = std::array::from_fn(|_| SplashEffect::default());
+        states_effect::clear_sound_effect(&mut self.sound_pool);
+        for touch in &mut self.touch_states {
+            touch.enable = false;
+            touch.touch_valid = false;
+        }
+        self.judge_events.clear();
+        self.chart_statistics.failed_at = None;
+        self.frame_time = time_in_second;
+        states_lines::tick_lines(&mut self.line_states, time_in_second);
+        states_statistics::refresh_chart_statistics(self);
+    }
+
+    /// Ticking all states, including lines, judges and chart statistics
+    pub fn tick_all(&mut self, time_in_second: f64, delta_time_in_second: f64, auto: bool) {
+        self.record(ReplayEvent::Tick {
+            time: time_in_second,
+            delta: delta_time_in_second,
+            auto,
+        });
+        self.play_origin = PlayOrigin::Ticked;
+        self.frame_time = time_in_second;
+        states_lines::tick_lines(&mut self.line_states, time_in_second);
+        states_effect::tick_effect(
+            &mut self.hit_effect_pool,
+            &mut self.splash_effect_pool,
+            delta_time_in_second,
+        );
+        let events_from = self.judge_events.len();
+        if self.play_mode.ends_run() && self.chart_statistics.failed_at.is_some() {
+            states_effect::clear_sound_effect(&mut self.sound_pool);
+        } else if states_judge::tick_lines_judge(self, delta_time_in_second, auto) {
+            states_statistics::count_judge_events(self, events_from);
+        }
+        if !self.collect_judge_events {
+            self.judge_events.clear();
+        }
+    }
+}
+
+/// Reset the state of notes that before the `before_time_in_second` to PERFECT
+pub fn reset_note_state(before_time_in_second: f64) {
+    ENGINE.with_borrow_mut(|it| it.reset_note_state(before_time_in_second));
+}
+
+/// Move the play to `time_in_second`, see `Engine::seek`
+pub fn seek(time_in_second: f64, policy: SeekPolicy) {
+    ENGINE.with_borrow_mut(|it| it.seek(time_in_second, policy));
 }
 
 /// Ticking all states, including lines, judges and chart statistics
 pub fn tick_all(time_in_second: f64, delta_time_in_second: f64, auto: bool) {
-    states_lines::tick_lines(time_in_second);
-    states_effect::tick_effect(delta_time_in_second);
-    if states_judge::tick_lines_judge(delta_time_in_second, auto) {
-        states_statistics::refresh_chart_statistics();
-    }
+    ENGINE.with_borrow_mut(|it| it.tick_all(time_in_second, delta_time_in_second, auto));
 }
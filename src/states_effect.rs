@@ -1,5 +1,8 @@
-use crate::{HIT_EFFECT_POOL, SOUND_POOL, SPLASH_EFFECT_POOL, chart::NoteType};
+use serde::{Deserialize, Serialize};
 
+use crate::chart::NoteType;
+
+#[derive(Serialize, Deserialize, Clone)]
 pub struct HitEffect {
     pub enable: bool,
     pub x: f64,
@@ -8,6 +11,7 @@ pub struct HitEffect {
     pub tint_type: i8,
 }
 
+#[derive(Serialize, Deserialize, Clone)]
 pub struct SplashEffect {
     pub enable: bool,
     pub x: f64,
@@ -20,7 +24,7 @@ pub struct SplashEffect {
 }
 
 #[allow(clippy::struct_field_names)]
-#[derive(Default)]
+#[derive(Serialize, Deserialize, Clone, Default)]
 pub struct SoundEffect {
     pub tap_count: i8,
     pub drag_count: i8,
@@ -79,91 +83,100 @@ impl Rng {
 
 const RATE: f64 = 2.0;
 
-pub(crate) fn tick_effect(delta_time_in_second: f64) {
-    HIT_EFFECT_POOL.with_borrow_mut(|pool| {
-        for it in pool.iter_mut() {
-            if it.enable {
-                it.progress += delta_time_in_second.max(0.0) * RATE;
-                if it.progress >= 1.0 {
-                    it.enable = false;
-                }
+pub(crate) fn tick_effect(
+    hit_effects: &mut [HitEffect],
+    splash_effects: &mut [SplashEffect],
+    delta_time_in_second: f64,
+) {
+    for it in hit_effects.iter_mut() {
+        if it.enable {
+            it.progress += delta_time_in_second.max(0.0) * RATE;
+            if it.progress >= 1.0 {
+                it.enable = false;
             }
         }
-    });
-    SPLASH_EFFECT_POOL.with_borrow_mut(|pool| {
-        for it in pool.iter_mut() {
-            if it.enable {
-                it.progress += delta_time_in_second.max(0.0) * RATE;
-                if it.progress >= 1.0 {
-                    it.enable = false;
-                    continue;
-                }
-                it.speed -= (it.speed * 7.0 * delta_time_in_second.max(0.0)).max(0.0);
-                it.x += it.speed * it.x_vec * delta_time_in_second.max(0.0);
-                it.y += it.speed * it.y_vec * delta_time_in_second.max(0.0);
+    }
+    for it in splash_effects.iter_mut() {
+        if it.enable {
+            it.progress += delta_time_in_second.max(0.0) * RATE;
+            if it.progress >= 1.0 {
+                it.enable = false;
+                continue;
             }
+            it.speed -= (it.speed * 7.0 * delta_time_in_second.max(0.0)).max(0.0);
+            it.x += it.speed * it.x_vec * delta_time_in_second.max(0.0);
+            it.y += it.speed * it.y_vec * delta_time_in_second.max(0.0);
         }
-    });
+    }
 }
 
-pub fn new_splash_effect(rng: &mut Rng, x: f64, y: f64, tint_type: i8, count: u8) {
+pub fn new_splash_effect(
+    pool: &mut [SplashEffect],
+    rng: &mut Rng,
+    x: f64,
+    y: f64,
+    tint_type: i8,
+    count: u8,
+) {
     let mut i = count;
-    SPLASH_EFFECT_POOL.with_borrow_mut(|pool| {
-        for effect in pool.iter_mut() {
-            if !effect.enable {
-                effect.enable = true;
-                effect.x = x;
-                effect.y = y;
-                let rand = rng.range(0.0, 2.0 * std::f64::consts::PI);
-                effect.x_vec = rand.cos();
-                effect.y_vec = rand.sin();
-                effect.speed = 2500.0;
-                effect.tint_type = tint_type;
-                effect.progress = 0.0;
-                i -= 1;
-                if i == 0 {
-                    return;
-                }
+    for effect in pool.iter_mut() {
+        if !effect.enable {
+            effect.enable = true;
+            effect.x = x;
+            effect.y = y;
+            let rand = rng.range(0.0, 2.0 * std::f64::consts::PI);
+            effect.x_vec = rand.cos();
+            effect.y_vec = rand.sin();
+            effect.speed = 2500.0;
+            effect.tint_type = tint_type;
+            effect.progress = 0.0;
+            i -= 1;
+            if i == 0 {
+                return;
             }
         }
-    });
+    }
 }
 
-pub fn new_click_effect(seed: f64, x: f64, y: f64, tint_type: i8) {
+pub fn new_click_effect(
+    hit_pool: &mut [HitEffect],
+    splash_pool: &mut [SplashEffect],
+    seed: f64,
+    x: f64,
+    y: f64,
+    tint_type: i8,
+) {
     let mut rng = Rng::new((seed * 114_514.0).to_bits());
-    HIT_EFFECT_POOL.with_borrow_mut(|pool| {
-        for effect in pool.iter_mut() {
-            if !effect.enable {
-                effect.enable = true;
-                effect.x = x;
-                effect.y = y;
-                effect.progress = 0.0;
-                effect.tint_type = tint_type;
-                new_splash_effect(
-                    &mut rng,
-                    x,
-                    y,
-                    tint_type,
-                    if effect.tint_type == 1 { 3 } else { 4 },
-                );
-                return;
-            }
+    for effect in hit_pool.iter_mut() {
+        if !effect.enable {
+            effect.enable = true;
+            effect.x = x;
+            effect.y = y;
+            effect.progress = 0.0;
+            effect.tint_type = tint_type;
+            new_splash_effect(
+                splash_pool,
+                &mut rng,
+                x,
+                y,
+                tint_type,
+                if effect.tint_type == 1 { 3 } else { 4 },
+            );
+            return;
         }
-    });
+    }
 }
 
-pub fn clear_sound_effect() {
-    SOUND_POOL.with_borrow_mut(|sounds| {
-        sounds.tap_count = 0;
-        sounds.drag_count = 0;
-        sounds.flick_count = 0;
-    });
+pub fn clear_sound_effect(sounds: &mut SoundEffect) {
+    sounds.tap_count = 0;
+    sounds.drag_count = 0;
+    sounds.flick_count = 0;
 }
 
-pub fn new_sound_effect(note_type: NoteType) {
-    SOUND_POOL.with_borrow_mut(|sounds| match note_type {
+pub fn new_sound_effect(sounds: &mut SoundEffect, note_type: NoteType) {
+    match note_type {
         NoteType::Tap | NoteType::Hold => sounds.tap_count += 1,
         NoteType::Drag => sounds.drag_count += 1,
         NoteType::Flick => sounds.flick_count += 1,
-    });
+    }
 }
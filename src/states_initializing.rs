@@ -1,46 +1,165 @@
 use std::{collections::HashSet, default::Default};
 
 use crate::{
-    CHART_STATISTICS, FLATTEN_NOTE_INDEX, HIT_EFFECT_POOL, LINE_STATES, SOUND_POOL,
-    SPLASH_EFFECT_POOL, TOUCH_STATES,
-    chart::{self, ChartRaw, JudgeLine, WithTimeRange},
-    input::TouchInfo,
+    ENGINE,
+    chart::{self, Chart, ChartRaw, JudgeLine, WithTimeRange},
+    chart_validation::{self, Severity},
+    engine::Engine,
+    error::ChartLoadError,
+    replay::PlayOrigin,
     states::{LineState, Metadata, NoteState, get_seconds_per_tick},
-    states_effect::{HitEffect, SoundEffect, SplashEffect},
-    states_statistics::{self, ChartStatistics},
+    states_statistics,
 };
 
+impl Engine {
+    /// Initialize state of lines from raw json.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the chart can not be loaded.
+    pub fn init_from_json(&mut self, json: &str) -> Result<Metadata, ChartLoadError> {
+        let chart_raw = ChartRaw::from_json(json)?;
+        self.try_init(chart_raw)
+    }
+
+    /// Initialize state of lines from the text of a `PhiEdit` chart.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the chart can not be parsed.
+    pub fn init_from_pec(&mut self, text: &str) -> Result<Metadata, ChartLoadError> {
+        let chart_raw = ChartRaw::from_pec(text)?;
+        self.try_init(chart_raw)
+    }
+
+    /// Initialize state of lines from standard V3 chart, rejecting the chart
+    /// if validation finds any error.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the chart is invalid, the states
+    /// are left untouched in that case.
+    pub fn try_init(&mut self, chart_raw: chart::ChartRaw) -> Result<Metadata, ChartLoadError> {
+        let (chart, metadata) = self.prepare(chart_raw);
+        if metadata
+            .diagnostics
+            .iter()
+            .any(|it| it.severity == Severity::Error)
+        {
+            return Err(ChartLoadError::Validation(metadata.diagnostics));
+        }
+        Ok(self.load(chart, metadata))
+    }
+
+    /// Initialize state of lines from standard V3 chart.
+    ///
+    /// The chart is validated, but loaded even if it is invalid. The problems
+    /// are reported in the `diagnostics` of the metadata.
+    pub fn init(&mut self, chart_raw: chart::ChartRaw) -> Metadata {
+        let (chart, metadata) = self.prepare(chart_raw);
+        self.load(chart, metadata)
+    }
+
+    /// Set whether the floor positions of notes are recomputed from the speed
+    /// events of their line when a chart is loaded, instead of trusting the
+    /// values stored in the chart. Disabled by default.
+    pub fn set_recompute_floor_position(&mut self, enable: bool) {
+        self.recompute_floor_position = enable;
+    }
+
+    fn prepare(&self, chart_raw: chart::ChartRaw) -> (Chart, Metadata) {
+        let format_version = chart_raw.format_version();
+        let (mut chart, conversion_report) = chart_raw.convert_to_v3_with_report();
+        let floor_position_report = self
+            .recompute_floor_position
+            .then(|| chart_validation::recompute_floor_positions(&mut chart));
+        let metadata = Metadata {
+            length_in_second: 0.0,
+            offset: chart.offset,
+            format_version,
+            conversion_report,
+            diagnostics: chart_validation::validate(&chart),
+            floor_position_report,
+        };
+        (chart, metadata)
+    }
+
+    fn load(&mut self, chart: Chart, metadata: Metadata) -> Metadata {
+        init_states(&mut self.line_states, chart);
+        self.judge_events.clear();
+        self.replay_recorder = None;
+        self.play_origin = PlayOrigin::Loaded;
+        self.chart_statistics.failed_at = None;
+        states_statistics::init_flatten_line_state(self);
+        Metadata {
+            length_in_second: get_estimated_length(&self.line_states),
+            ..metadata
+        }
+    }
+
+    /// Clear the states of lines
+    pub fn clear(&mut self) {
+        *self = Engine {
+            draw_image_offset: std::mem::take(&mut self.draw_image_offset),
+            recompute_floor_position: self.recompute_floor_position,
+            judge_config: self.judge_config,
+            combo_order: self.combo_order,
+            play_mode: self.play_mode,
+            collect_judge_events: self.collect_judge_events,
+            ..Engine::default()
+        };
+    }
+}
+
 /// Initialize state of lines from raw json.
 ///
 /// # Errors
 ///
-/// This function will return an error if the deserialization failed.
-pub fn init_line_states_from_json(json: &str) -> Result<Metadata, serde_json::Error> {
-    let chart_raw = serde_json::from_str::<ChartRaw>(json)?;
-    Ok(init_line_states(chart_raw))
+/// This function will return an error if the chart can not be loaded.
+pub fn init_line_states_from_json(json: &str) -> Result<Metadata, ChartLoadError> {
+    ENGINE.with_borrow_mut(|it| it.init_from_json(json))
+}
+
+/// Initialize state of lines from the text of a `PhiEdit` chart.
+///
+/// # Errors
+///
+/// This function will return an error if the chart can not be parsed.
+pub fn init_line_states_from_pec(text: &str) -> Result<Metadata, ChartLoadError> {
+    ENGINE.with_borrow_mut(|it| it.init_from_pec(text))
 }
 
 /// Initialize state of lines from standard V3 chart
 #[must_use]
 pub fn init_line_states(chart_raw: chart::ChartRaw) -> Metadata {
-    let format_version = match chart_raw {
-        ChartRaw::V1(_) => 1,
-        ChartRaw::V3(_) => 3,
-    };
-    let mut chart = chart_raw.convert_to_v3();
+    ENGINE.with_borrow_mut(|it| it.init(chart_raw))
+}
+
+/// Set whether the floor positions of notes are recomputed from the speed
+/// events of their line when a chart is loaded
+pub fn set_recompute_floor_position(enable: bool) {
+    ENGINE.with_borrow_mut(|it| it.set_recompute_floor_position(enable));
+}
+
+/// Clear the states of lines
+pub fn clear_states() {
+    ENGINE.with_borrow_mut(Engine::clear);
+}
+
+fn init_states(states: &mut Vec<LineState>, mut chart: Chart) {
     chart.judge_line_list = chart
         .judge_line_list
         .into_iter()
         .map(|mut line| {
-            line.notes_above.sort_by(|a, b| (a.time).cmp(&b.time));
-            line.notes_below.sort_by(|a, b| (a.time).cmp(&b.time));
+            line.notes_above.sort_by_key(|it| it.time);
+            line.notes_below.sort_by_key(|it| it.time);
             line
         })
         .collect::<Vec<_>>();
-    let metadata = LINE_STATES.with_borrow_mut(|states| {
-        *states = std::array::from_fn(|_| LineState::default());
-        let available_len = chart.judge_line_list.len();
-        for (i, it) in chart.judge_line_list.into_iter().enumerate() {
+    *states = chart
+        .judge_line_list
+        .into_iter()
+        .map(|it| {
             let JudgeLine {
                 bpm,
                 notes_above,
@@ -50,7 +169,7 @@ pub fn init_line_states(chart_raw: chart::ChartRaw) -> Metadata {
                 rotate_events,
                 alpha_events,
             } = it;
-            states[i] = LineState {
+            LineState {
                 enable: true,
                 bpm,
                 move_events,
@@ -58,7 +177,6 @@ pub fn init_line_states(chart_raw: chart::ChartRaw) -> Metadata {
                 speed_events,
                 rotate_events,
                 notes_above_state: notes_above
-                    .clone()
                     .into_iter()
                     .map(|it| NoteState {
                         note: it,
@@ -66,7 +184,6 @@ pub fn init_line_states(chart_raw: chart::ChartRaw) -> Metadata {
                     })
                     .collect(),
                 notes_below_state: notes_below
-                    .clone()
                     .into_iter()
                     .map(|it| NoteState {
                         note: it,
@@ -75,31 +192,9 @@ pub fn init_line_states(chart_raw: chart::ChartRaw) -> Metadata {
                     .collect(),
                 ..Default::default()
             }
-        }
-        states
-            .iter_mut()
-            .skip(available_len)
-            .for_each(|it| it.enable = false);
-        process_highlight(states.as_mut());
-        Metadata {
-            length_in_second: get_estimated_length(states),
-            offset: chart.offset,
-            format_version,
-        }
-    });
-    states_statistics::init_flatten_line_state();
-    metadata
-}
-
-/// Clear the states of lines
-pub fn clear_states() {
-    FLATTEN_NOTE_INDEX.with_borrow_mut(std::vec::Vec::clear);
-    LINE_STATES.with_borrow_mut(|it| *it = std::array::from_fn(|_| LineState::default()));
-    TOUCH_STATES.with_borrow_mut(|it| *it = std::array::from_fn(|_| TouchInfo::default()));
-    HIT_EFFECT_POOL.with_borrow_mut(|it| *it = std::array::from_fn(|_| HitEffect::default()));
-    SPLASH_EFFECT_POOL.with_borrow_mut(|it| *it = std::array::from_fn(|_| SplashEffect::default()));
-    CHART_STATISTICS.with_borrow_mut(|it| *it = ChartStatistics::default());
-    SOUND_POOL.with_borrow_mut(|it| *it = SoundEffect::default());
+        })
+        .collect();
+    process_highlight(states);
 }
 
 fn process_highlight(judge_line_states: &mut [LineState]) {
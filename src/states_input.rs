@@ -1,37 +1,55 @@
-use crate::TOUCH_STATES;
+use crate::{ENGINE, engine::Engine, replay::ReplayEvent};
 
-/// Set a touch point as enabled
-pub fn set_touch_down(id: usize, x: f32, y: f32) {
-    TOUCH_STATES.with_borrow_mut(|it| {
-        if let Some(touch) = it.get_mut(id) {
+impl Engine {
+    /// Set a touch point as enabled
+    pub fn set_touch_down(&mut self, id: usize, x: f32, y: f32) {
+        if let Some(touch) = self.touch_states.get_mut(id) {
             touch.touch_down(x, y);
+            self.record(ReplayEvent::TouchDown { id: id as u8, x, y });
+        }
+    }
+
+    /// Move a touch point
+    pub fn set_touch_move(&mut self, id: usize, x: f32, y: f32) {
+        if let Some(touch) = self.touch_states.get_mut(id) {
+            touch.touch_move(x, y);
+            self.record(ReplayEvent::TouchMove { id: id as u8, x, y });
+        }
+    }
+
+    /// Set a touch point as disabled
+    pub fn set_touch_up(&mut self, id: usize) {
+        if let Some(touch) = self.touch_states.get_mut(id) {
+            touch.touch_up();
+            self.record(ReplayEvent::TouchUp { id: id as u8 });
+        }
+    }
+
+    /// Clear the state of touch
+    pub fn clear_touch(&mut self) {
+        for touch in &mut self.touch_states {
+            touch.enable = false;
         }
-    });
+        self.record(ReplayEvent::ClearTouch);
+    }
+}
+
+/// Set a touch point as enabled
+pub fn set_touch_down(id: usize, x: f32, y: f32) {
+    ENGINE.with_borrow_mut(|it| it.set_touch_down(id, x, y));
 }
 
 /// Move a touch point
 pub fn set_touch_move(id: usize, x: f32, y: f32) {
-    TOUCH_STATES.with_borrow_mut(|it| {
-        if let Some(touch) = it.get_mut(id) {
-            touch.touch_move(x, y);
-        }
-    });
+    ENGINE.with_borrow_mut(|it| it.set_touch_move(id, x, y));
 }
 
 /// Set a touch point as disabled
 pub fn set_touch_up(id: usize) {
-    TOUCH_STATES.with_borrow_mut(|it| {
-        if let Some(touch) = it.get_mut(id) {
-            touch.touch_up();
-        }
-    });
+    ENGINE.with_borrow_mut(|it| it.set_touch_up(id));
 }
 
 /// Clear the state of touch
 pub fn clear_touch() {
-    TOUCH_STATES.with_borrow_mut(|it| {
-        for touch in it.iter_mut() {
-            touch.enable = false;
-        }
-    });
+    ENGINE.with_borrow_mut(Engine::clear_touch);
 }
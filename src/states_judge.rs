@@ -1,108 +1,173 @@
+use serde::{Deserialize, Serialize};
+
 use crate::{
-    LINE_STATES, TOUCH_STATES,
+    ENGINE,
     chart::{Note, NoteType},
+    engine::Engine,
     input::TouchInfo,
+    judge_config::JudgeConfig,
     math::{self, Point},
-    states::{LineState, NoteScore, NoteState},
-    states_effect,
+    states::{LineState, NoteScore, NoteState, get_seconds_per_tick},
+    states_effect::{self, HitEffect, SoundEffect, SplashEffect},
 };
 
-pub(crate) fn tick_lines_judge(delta_time_in_second: f64, auto: bool) -> bool {
-    states_effect::clear_sound_effect();
-    TOUCH_STATES.with_borrow_mut(|touches| {
-        LINE_STATES.with_borrow_mut(|lines| {
-            tick_line_judge(delta_time_in_second, touches.as_mut(), lines.as_mut(), auto)
-        })
-    })
+/// What happened to a note
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum JudgeEventKind {
+    /// The note was hit, or the hold note was held to its end
+    Hit,
+
+    /// The note was missed
+    Miss,
+
+    /// The head of the hold note was hit
+    HoldStart,
+
+    /// The hold note is still held, emitted periodically
+    HoldTick,
+
+    /// The hold note was released before its end
+    HoldBreak,
+}
+
+/// A judgement of a note, produced while ticking
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct JudgeEvent {
+    /// What happened to the note
+    pub kind: JudgeEventKind,
+
+    /// The index of the judge line
+    pub line: usize,
+
+    /// Whether the note is above the line
+    pub above: bool,
+
+    /// The index of the note on its side of the line, ordered by time
+    pub note_index: usize,
+
+    /// The type of the note
+    pub note_type: NoteType,
+
+    /// The grade of the note, the grade of the head for hold events
+    pub grade: NoteScore,
+
+    /// The signed offset of the hit in milliseconds, negative when early.
+    /// Drag and flick notes have none.
+    pub offset_ms: Option<f64>,
+
+    /// The position of the note on the line, in world coordinates
+    pub position: (f64, f64),
+
+    /// The time of the judgement in seconds
+    pub time: f64,
+}
+
+impl Engine {
+    /// Set whether judge events are kept for `drain_judge_events`, off by
+    /// default. Turning it off drops the events not drained yet.
+    pub fn set_collect_judge_events(&mut self, collect: bool) {
+        self.collect_judge_events = collect;
+        if !collect {
+            self.judge_events.clear();
+        }
+    }
+
+    /// Take the judge events produced since the last call. The events
+    /// accumulate over ticks until they are drained, and are only kept after
+    /// `set_collect_judge_events(true)`.
+    pub fn drain_judge_events(&mut self) -> Vec<JudgeEvent> {
+        std::mem::take(&mut self.judge_events)
+    }
+}
+
+/// Set whether judge events are kept, see `Engine::set_collect_judge_events`
+pub fn set_collect_judge_events(collect: bool) {
+    ENGINE.with_borrow_mut(|it| it.set_collect_judge_events(collect));
+}
+
+/// Take the judge events produced since the last call, see
+/// `Engine::drain_judge_events`
+#[must_use]
+pub fn drain_judge_events() -> Vec<JudgeEvent> {
+    ENGINE.with_borrow_mut(Engine::drain_judge_events)
+}
+
+struct JudgeContext<'a> {
+    touches: &'a mut [TouchInfo],
+    hit_effects: &'a mut [HitEffect],
+    splash_effects: &'a mut [SplashEffect],
+    sounds: &'a mut SoundEffect,
+    config: &'a JudgeConfig,
+    events: &'a mut Vec<JudgeEvent>,
+    hold_ticked: bool,
+}
+
+pub(crate) fn tick_lines_judge(engine: &mut Engine, delta_time_in_second: f64, auto: bool) -> bool {
+    states_effect::clear_sound_effect(&mut engine.sound_pool);
+    let mut context = JudgeContext {
+        touches: &mut engine.touch_states,
+        hit_effects: &mut engine.hit_effect_pool,
+        splash_effects: &mut engine.splash_effect_pool,
+        sounds: &mut engine.sound_pool,
+        config: &engine.judge_config,
+        events: &mut engine.judge_events,
+        hold_ticked: false,
+    };
+    tick_line_judge(
+        delta_time_in_second,
+        &mut context,
+        &mut engine.line_states,
+        auto,
+    )
 }
 
 fn tick_line_judge(
     delta_time_in_second: f64,
-    touches: &mut [TouchInfo],
+    context: &mut JudgeContext,
     lines: &mut [LineState],
     auto: bool,
 ) -> bool {
     let mut judged = false;
-    for line in lines.iter_mut() {
+    for (line_index, line) in lines.iter_mut().enumerate() {
         if !line.enable {
             continue;
         }
         let current_tick = line.tick_time;
-        line.notes_above_state
-            .iter_mut()
-            .chain(line.notes_below_state.iter_mut())
-            .for_each(|note| {
-                let line_x = line.x;
-                let line_y = line.y;
-                let line_rotate = line.rotate;
-                let bpm = line.bpm;
-                let note_type = note.note.r#type;
-                let local_judged = if auto {
-                    match note_type {
-                        NoteType::Hold => tick_hold_note_auto(
-                            delta_time_in_second,
-                            current_tick,
-                            note,
-                            touches,
-                            line_x,
-                            line_y,
-                            line_rotate,
-                            bpm,
-                        ),
-                        _ => tick_normal_note_auto(
-                            current_tick,
-                            note,
-                            line_x,
-                            line_y,
-                            line_rotate,
-                            bpm,
-                        ),
-                    }
-                } else {
-                    match note_type {
-                        NoteType::Tap => tick_tap_note(
-                            current_tick,
-                            note,
-                            touches,
-                            line_x,
-                            line_y,
-                            line_rotate,
-                            bpm,
-                        ),
-                        NoteType::Drag => tick_drag_note(
-                            current_tick,
-                            note,
-                            touches,
-                            line_x,
-                            line_y,
-                            line_rotate,
-                            bpm,
-                        ),
-                        NoteType::Hold => tick_hold_note(
-                            delta_time_in_second,
-                            current_tick,
-                            note,
-                            touches,
-                            line_x,
-                            line_y,
-                            line_rotate,
-                            bpm,
-                        ),
-                        NoteType::Flick => tick_flick_note(
-                            current_tick,
-                            note,
-                            touches,
-                            line_x,
-                            line_y,
-                            line_rotate,
-                            bpm,
-                        ),
-                    }
+        let line_x = line.x;
+        let line_y = line.y;
+        let line_rotate = line.rotate;
+        let bpm = line.bpm;
+        let sides = [
+            (true, &mut line.notes_above_state),
+            (false, &mut line.notes_below_state),
+        ];
+        for (above, notes) in sides {
+            for (note_index, note) in notes.iter_mut().enumerate() {
+                let before = (note.score, note.extra_score);
+                context.hold_ticked = false;
+                judged |= tick_note(
+                    delta_time_in_second,
+                    current_tick,
+                    note,
+                    context,
+                    line_x,
+                    line_y,
+                    line_rotate,
+                    bpm,
+                    auto,
+                );
+                let source = JudgeEventSource {
+                    line: line_index,
+                    above,
+                    note_index,
+                    time: current_tick * get_seconds_per_tick(bpm),
+                    line_pose: (line_x, line_y, line_rotate),
                 };
-                judged |= local_judged;
-            });
+                push_judge_events(context, before, note, &source);
+            }
+        }
     }
-    for touch in touches.iter_mut() {
+    for touch in context.touches.iter_mut() {
         if touch.enable {
             touch.touch_valid = false;
         }
@@ -110,6 +175,140 @@ fn tick_line_judge(
     judged
 }
 
+#[allow(clippy::too_many_arguments)]
+fn tick_note(
+    delta_time_in_second: f64,
+    current_tick: f64,
+    note: &mut NoteState,
+    context: &mut JudgeContext,
+    line_x: f64,
+    line_y: f64,
+    line_rotate: f64,
+    bpm: f64,
+    auto: bool,
+) -> bool {
+    let note_type = note.note.r#type;
+    if auto {
+        match note_type {
+            NoteType::Hold => tick_hold_note_auto(
+                delta_time_in_second,
+                current_tick,
+                note,
+                context,
+                line_x,
+                line_y,
+                line_rotate,
+                bpm,
+            ),
+            _ => tick_normal_note_auto(
+                current_tick,
+                note,
+                context,
+                line_x,
+                line_y,
+                line_rotate,
+                bpm,
+            ),
+        }
+    } else {
+        match note_type {
+            NoteType::Tap => tick_tap_note(
+                current_tick,
+                note,
+                context,
+                line_x,
+                line_y,
+                line_rotate,
+                bpm,
+            ),
+            NoteType::Drag => tick_drag_note(
+                current_tick,
+                note,
+                context,
+                line_x,
+                line_y,
+                line_rotate,
+                bpm,
+            ),
+            NoteType::Hold => tick_hold_note(
+                delta_time_in_second,
+                current_tick,
+                note,
+                context,
+                line_x,
+                line_y,
+                line_rotate,
+                bpm,
+            ),
+            NoteType::Flick => tick_flick_note(
+                current_tick,
+                note,
+                context,
+                line_x,
+                line_y,
+                line_rotate,
+                bpm,
+            ),
+        }
+    }
+}
+
+struct JudgeEventSource {
+    line: usize,
+    above: bool,
+    note_index: usize,
+    time: f64,
+    line_pose: (f64, f64, f64),
+}
+
+/// Push the events caused by the change of the note in this tick
+fn push_judge_events(
+    context: &mut JudgeContext,
+    (score_before, extra_score_before): (NoteScore, NoteScore),
+    note: &NoteState,
+    source: &JudgeEventSource,
+) {
+    let note_type = note.note.r#type;
+    let mut push = |kind: JudgeEventKind, grade: NoteScore| {
+        let (line_x, line_y, line_rotate) = source.line_pose;
+        let Point { x, y } = math::get_pos_out_of_line(
+            line_x,
+            line_y,
+            line_rotate,
+            note.note.position_x * math::UNIT_WIDTH,
+        );
+        context.events.push(JudgeEvent {
+            kind,
+            line: source.line,
+            above: source.above,
+            note_index: source.note_index,
+            note_type,
+            grade,
+            offset_ms: note.hit_offset,
+            position: (x, y),
+            time: source.time,
+        });
+    };
+    if note_type == NoteType::Hold {
+        if extra_score_before == NoteScore::None && note.extra_score != NoteScore::None {
+            push(JudgeEventKind::HoldStart, note.extra_score);
+        }
+        if context.hold_ticked {
+            push(JudgeEventKind::HoldTick, note.extra_score);
+        }
+    }
+    if score_before != NoteScore::None || note.score == NoteScore::None {
+        return;
+    }
+    match note.score {
+        NoteScore::Miss if note_type == NoteType::Hold && note.extra_score != NoteScore::None => {
+            push(JudgeEventKind::HoldBreak, NoteScore::Miss);
+        }
+        NoteScore::Miss => push(JudgeEventKind::Miss, NoteScore::Miss),
+        grade => push(JudgeEventKind::Hit, grade),
+    }
+}
+
 fn check_point_in_judge_range(
     line_x: f64,
     line_y: f64,
@@ -156,34 +355,37 @@ fn check_point_in_judge_range(
     )
 }
 
-fn check_judge_result(current_tick: f64, note: &NoteState, bpm: f64) -> (f64, NoteScore) {
+fn check_judge_result(
+    current_tick: f64,
+    note: &NoteState,
+    bpm: f64,
+    config: &JudgeConfig,
+) -> (f64, NoteScore) {
     let seconds_per_tick = 60.0 / bpm / 32.0;
-    let perfect_range_in_tick = 0.08 / seconds_per_tick;
-    let good_range_in_tick = 0.16 / seconds_per_tick;
-    let bad_range_in_tick = 0.18 / seconds_per_tick;
-    let time_delta = current_tick - f64::from(note.note.time);
-    (
-        time_delta,
-        match time_delta.abs() {
-            x if 0.0 <= x && x <= perfect_range_in_tick => NoteScore::Perfect,
-            x if perfect_range_in_tick < x && x <= good_range_in_tick => NoteScore::Good,
-            x if good_range_in_tick < x && x <= bad_range_in_tick => NoteScore::Bad,
-            _ => NoteScore::Miss,
-        },
-    )
+    let time_delta = (current_tick - f64::from(note.note.time)) * seconds_per_tick;
+    (time_delta * 1000.0, config.grade(time_delta))
 }
 
-fn create_splash(seed: f64, x: f64, y: f64, note_score: NoteScore) {
-    match note_score {
-        NoteScore::Perfect => states_effect::new_click_effect(seed, x, y, 0),
-        NoteScore::Good => states_effect::new_click_effect(seed, x, y, 1),
-        _ => {}
-    }
+fn create_splash(context: &mut JudgeContext, seed: f64, x: f64, y: f64, note_score: NoteScore) {
+    let tint_type = match note_score {
+        NoteScore::Perfect => 0,
+        NoteScore::Good => 1,
+        _ => return,
+    };
+    states_effect::new_click_effect(
+        context.hit_effects,
+        context.splash_effects,
+        seed,
+        x,
+        y,
+        tint_type,
+    );
 }
 
 fn tick_normal_note_auto(
     current_tick: f64,
     note: &mut NoteState,
+    context: &mut JudgeContext,
     line_x: f64,
     line_y: f64,
     line_rotate: f64,
@@ -192,7 +394,7 @@ fn tick_normal_note_auto(
     if note.score != NoteScore::None {
         return false;
     }
-    let (judge_delta, _) = check_judge_result(current_tick, note, bpm);
+    let (judge_delta, _) = check_judge_result(current_tick, note, bpm, context.config);
     if judge_delta >= 0.0 {
         let Point {
             x: root_x,
@@ -204,8 +406,12 @@ fn tick_normal_note_auto(
             note.note.position_x * math::UNIT_WIDTH,
         );
         note.score = NoteScore::Perfect;
-        create_splash(current_tick, root_x, root_y, NoteScore::Perfect);
-        states_effect::new_sound_effect(note.note.r#type);
+        // Autoplay hits on time, the delta is only the lateness of the tick
+        if note.note.r#type == NoteType::Tap {
+            note.hit_offset = Some(0.0);
+        }
+        create_splash(context, current_tick, root_x, root_y, NoteScore::Perfect);
+        states_effect::new_sound_effect(context.sounds, note.note.r#type);
         return true;
     }
     false
@@ -214,7 +420,7 @@ fn tick_normal_note_auto(
 fn tick_flick_note(
     current_tick: f64,
     note: &mut NoteState,
-    touches: &mut [TouchInfo],
+    context: &mut JudgeContext,
     line_x: f64,
     line_y: f64,
     line_rotate: f64,
@@ -223,7 +429,7 @@ fn tick_flick_note(
     if note.score != NoteScore::None {
         return false;
     }
-    let (judge_delta, judge_result) = check_judge_result(current_tick, note, bpm);
+    let (judge_delta, judge_result) = check_judge_result(current_tick, note, bpm, context.config);
     if judge_delta < 0.0 && judge_result == NoteScore::Miss {
         return false;
     }
@@ -239,8 +445,8 @@ fn tick_flick_note(
                 note.note.position_x * math::UNIT_WIDTH,
             );
             note.score = NoteScore::Perfect;
-            create_splash(current_tick, root_x, root_y, NoteScore::Perfect);
-            states_effect::new_sound_effect(NoteType::Flick);
+            create_splash(context, current_tick, root_x, root_y, NoteScore::Perfect);
+            states_effect::new_sound_effect(context.sounds, NoteType::Flick);
             return true;
         }
         return false;
@@ -249,13 +455,14 @@ fn tick_flick_note(
         note.score = NoteScore::Miss;
         return true;
     }
-    for touch in touches {
+    for touch in context.touches.iter_mut() {
         if !touch.enable {
             continue;
         }
         let (is_in_judge_range, _) =
             check_point_in_judge_range(line_x, line_y, line_rotate, &note.note, touch);
         if is_in_judge_range && touch.length() >= 50.0 {
+            // the flick is perfect whenever it is flicked, so it has no offset
             note.extra_score = NoteScore::Perfect;
             touch.reset_length();
             return false;
@@ -269,7 +476,7 @@ fn tick_hold_note_auto(
     delta_time_in_second: f64,
     current_tick: f64,
     note: &mut NoteState,
-    touches: &mut [TouchInfo],
+    context: &mut JudgeContext,
     line_x: f64,
     line_y: f64,
     line_rotate: f64,
@@ -278,16 +485,17 @@ fn tick_hold_note_auto(
     if note.score != NoteScore::None {
         return false;
     }
-    let (judge_delta, _) = check_judge_result(current_tick, note, bpm);
+    let (judge_delta, _) = check_judge_result(current_tick, note, bpm, context.config);
     if judge_delta >= 0.0 && note.extra_score != NoteScore::Perfect {
         note.extra_score = NoteScore::Perfect;
-        states_effect::new_sound_effect(NoteType::Hold);
+        note.hit_offset = Some(0.0);
+        states_effect::new_sound_effect(context.sounds, NoteType::Hold);
     }
     tick_hold_note_common(
         delta_time_in_second,
         current_tick,
         note,
-        touches,
+        context,
         line_x,
         line_y,
         line_rotate,
@@ -302,7 +510,7 @@ fn tick_hold_note_common(
     delta_time_in_second: f64,
     current_tick: f64,
     note: &mut NoteState,
-    touches: &mut [TouchInfo],
+    context: &mut JudgeContext,
     line_x: f64,
     line_y: f64,
     line_rotate: f64,
@@ -325,7 +533,7 @@ fn tick_hold_note_common(
                 note.note.position_x * math::UNIT_WIDTH,
             );
             if auto
-                || touches.iter().any(|touch| {
+                || context.touches.iter().any(|touch| {
                     let (is_in_judge_range, _) =
                         check_point_in_judge_range(line_x, line_y, line_rotate, &note.note, touch);
                     is_in_judge_range && touch.enable
@@ -336,7 +544,8 @@ fn tick_hold_note_common(
                 } else {
                     note.hold_cool_down + 16.0
                 };
-                create_splash(current_tick, root_x, root_y, note.extra_score);
+                create_splash(context, current_tick, root_x, root_y, note.extra_score);
+                context.hold_ticked = true;
             } else {
                 note.score = NoteScore::Miss;
                 judged = true;
@@ -356,7 +565,7 @@ fn tick_hold_note(
     delta_time_in_second: f64,
     current_tick: f64,
     note: &mut NoteState,
-    touches: &mut [TouchInfo],
+    context: &mut JudgeContext,
     line_x: f64,
     line_y: f64,
     line_rotate: f64,
@@ -369,7 +578,7 @@ fn tick_hold_note(
         delta_time_in_second,
         current_tick,
         note,
-        touches,
+        context,
         line_x,
         line_y,
         line_rotate,
@@ -379,7 +588,7 @@ fn tick_hold_note(
     if hold {
         return hold_judged;
     }
-    let (judge_delta, judge_result) = check_judge_result(current_tick, note, bpm);
+    let (judge_delta, judge_result) = check_judge_result(current_tick, note, bpm, context.config);
     if judge_delta < 0.0 && judge_result == NoteScore::Miss {
         return false;
     }
@@ -387,7 +596,7 @@ fn tick_hold_note(
         note.score = NoteScore::Miss;
         return true;
     }
-    for touch in touches {
+    for touch in context.touches.iter_mut() {
         if !touch.enable {
             continue;
         }
@@ -399,7 +608,8 @@ fn tick_hold_note(
             }
             touch.touch_valid = false;
             note.extra_score = judge_result;
-            states_effect::new_sound_effect(NoteType::Hold);
+            note.hit_offset = Some(judge_delta);
+            states_effect::new_sound_effect(context.sounds, NoteType::Hold);
             return false;
         }
     }
@@ -409,7 +619,7 @@ fn tick_hold_note(
 fn tick_drag_note(
     current_tick: f64,
     note: &mut NoteState,
-    touches: &mut [TouchInfo],
+    context: &mut JudgeContext,
     line_x: f64,
     line_y: f64,
     line_rotate: f64,
@@ -418,7 +628,7 @@ fn tick_drag_note(
     if note.score != NoteScore::None {
         return false;
     }
-    let (judge_delta, judge_result) = check_judge_result(current_tick, note, bpm);
+    let (judge_delta, judge_result) = check_judge_result(current_tick, note, bpm, context.config);
     if judge_delta < 0.0 && judge_result == NoteScore::Miss {
         return false;
     }
@@ -434,8 +644,8 @@ fn tick_drag_note(
                 note.note.position_x * math::UNIT_WIDTH,
             );
             note.score = NoteScore::Perfect;
-            states_effect::new_sound_effect(NoteType::Drag);
-            create_splash(current_tick, root_x, root_y, NoteScore::Perfect);
+            states_effect::new_sound_effect(context.sounds, NoteType::Drag);
+            create_splash(context, current_tick, root_x, root_y, NoteScore::Perfect);
             return true;
         }
         return false;
@@ -444,13 +654,14 @@ fn tick_drag_note(
         note.score = NoteScore::Miss;
         return true;
     }
-    for touch in touches {
+    for touch in context.touches.iter_mut() {
         if !touch.enable {
             continue;
         }
         let (is_in_judge_range, _) =
             check_point_in_judge_range(line_x, line_y, line_rotate, &note.note, touch);
         if is_in_judge_range {
+            // the drag is perfect whenever it is touched, so it has no offset
             note.extra_score = NoteScore::Perfect;
             return false;
         }
@@ -461,7 +672,7 @@ fn tick_drag_note(
 fn tick_tap_note(
     current_tick: f64,
     note: &mut NoteState,
-    touches: &mut [TouchInfo],
+    context: &mut JudgeContext,
     line_x: f64,
     line_y: f64,
     line_rotate: f64,
@@ -470,7 +681,7 @@ fn tick_tap_note(
     if note.score != NoteScore::None {
         return false;
     }
-    let (judge_delta, judge_result) = check_judge_result(current_tick, note, bpm);
+    let (judge_delta, judge_result) = check_judge_result(current_tick, note, bpm, context.config);
     if judge_delta < 0.0 && judge_result == NoteScore::Miss {
         return false;
     }
@@ -479,7 +690,7 @@ fn tick_tap_note(
         note.score = NoteScore::Miss;
         return true;
     }
-    for touch in touches {
+    for touch in context.touches.iter_mut() {
         if !touch.enable {
             continue;
         }
@@ -488,8 +699,9 @@ fn tick_tap_note(
         if is_in_judge_range && touch.touch_valid {
             touch.touch_valid = false;
             note.score = judge_result;
-            states_effect::new_sound_effect(NoteType::Tap);
-            create_splash(current_tick, root_x, root_y, judge_result);
+            note.hit_offset = Some(judge_delta);
+            states_effect::new_sound_effect(context.sounds, NoteType::Tap);
+            create_splash(context, current_tick, root_x, root_y, judge_result);
             return true;
         }
     }
@@ -1,22 +1,31 @@
 use crate::{
-    LINE_STATES,
     chart::{self, TimeState, WithTimeRange, WithValue},
     math,
-    states::LineState,
+    states::{LineState, get_seconds_per_tick},
 };
 
-pub(crate) fn tick_lines(time_in_second: f64) {
-    LINE_STATES.with_borrow_mut(|x| {
-        for state in x.iter_mut() {
-            tick_line_state(time_in_second, state);
-        }
-    });
+pub(crate) fn tick_lines(line_states: &mut [LineState], time_in_second: f64) {
+    for state in line_states.iter_mut() {
+        tick_line_state(time_in_second, state);
+    }
 }
 
 fn get_line_y(tick_time: f64, line: &LineState) -> f64 {
+    get_floor_position(
+        &line.speed_events,
+        get_seconds_per_tick(line.bpm),
+        tick_time,
+    )
+}
+
+/// Get the distance the line has travelled at `tick_time`, integrated over
+/// the speed events.
+pub(crate) fn get_floor_position(
+    speed_events: &[chart::Event1],
+    seconds_per_tick: f64,
+    tick_time: f64,
+) -> f64 {
     let mut t = 0.0;
-    let seconds_per_tick = 60.0 / line.bpm / 32.0;
-    let speed_events = &line.speed_events;
     for event in speed_events {
         if event.end_time > tick_time && event.start_time > tick_time {
             break;
@@ -27,7 +36,7 @@ fn get_line_y(tick_time: f64, line: &LineState) -> f64 {
             t += duration * percent * event.value;
             break;
         }
-        if event.end_time < tick_time {
+        if event.end_time <= tick_time {
             t += (event.end_time - event.start_time) * event.value;
         }
     }
@@ -1,6 +1,13 @@
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+
 use crate::{
-    CHART_STATISTICS, FLATTEN_NOTE_INDEX, LINE_STATES,
-    states::{self, LineState, NoteState},
+    ENGINE,
+    engine::Engine,
+    replay::ReplayEvent,
+    states::{self, LineState, NoteScore, NoteState},
+    states_judge::JudgeEventKind,
 };
 
 pub struct NoteIndex {
@@ -10,11 +17,134 @@ pub struct NoteIndex {
     pub time_in_second: f64,
 }
 
+/// Running totals that keep the statistics up to date without walking every
+/// note on each judgement
+#[derive(Default)]
+pub(crate) struct StatisticsTally {
+    /// The position in the flatten index of every note, by line, above first
+    positions: Vec<[Vec<usize>; 2]>,
+    /// The first position in the flatten index that is not judged yet
+    cursor: usize,
+    /// The combo and max combo of the notes before the cursor, or of every
+    /// judged note when the combo is in judgment order
+    prefix_combo: (u32, u32),
+    /// The judged positions after the cursor
+    pending: BTreeSet<usize>,
+    /// The judge sequence of the next judged note
+    next_sequence: u32,
+    total_notes: u32,
+    perfect: u32,
+    good: u32,
+    /// The number of bad and missed notes
+    broken: u32,
+    offset_count: u32,
+    offset_mean: f64,
+    /// The sum of squared distances from the mean, for the standard deviation
+    offset_m2: f64,
+}
+
+/// The order in which judged notes build up the combo
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum ComboOrder {
+    /// The order the notes were judged in, a hold counts when its tail is
+    /// judged. Notes judged in the same tick are ordered by their end time.
+    #[default]
+    Judgment,
+
+    /// The order of the time the notes end, regardless of when they were
+    /// judged. A judgement can change the combo of notes that were judged
+    /// before it.
+    EndTime,
+}
+
+/// The statistics of the current play
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct ChartStatistics {
+    /// The current combo
     pub combo: u32,
+
+    /// The max combo so far
     pub max_combo: u32,
+
+    /// The score, up to 1,000,000
     pub score: f64,
+
+    /// The accuracy, from 0 to 1
     pub accurate: f64,
+
+    /// Early and late counts of perfect hits
+    pub perfect_timing: TimingCount,
+
+    /// Early and late counts of good hits
+    pub good_timing: TimingCount,
+
+    /// Early and late counts of bad hits
+    pub bad_timing: TimingCount,
+
+    /// The mean of the hit offsets in milliseconds, negative when early. Drag
+    /// and flick notes have no offset.
+    pub mean_offset: f64,
+
+    /// The standard deviation of the hit offsets in milliseconds
+    pub offset_std_dev: f64,
+
+    /// The rank of the score
+    pub rank: Rank,
+
+    /// Whether the run is still a full combo or all perfect
+    pub combo_status: ComboStatus,
+
+    /// The time in seconds of the judgement that failed the run, see
+    /// `PlayMode`
+    pub failed_at: Option<f64>,
+}
+
+/// The rank of a score, as shown by the official game
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Rank {
+    /// 1,000,000
+    Phi,
+
+    /// 960,000 and above
+    V,
+
+    /// 920,000 and above
+    S,
+
+    /// 880,000 and above
+    A,
+
+    /// 820,000 and above
+    B,
+
+    /// 700,000 and above
+    C,
+
+    /// Below 700,000
+    F,
+}
+
+/// Whether every judged note keeps the run a full combo or all perfect
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ComboStatus {
+    /// Every judged note is perfect
+    AllPerfect,
+
+    /// Every judged note is perfect or good
+    FullCombo,
+
+    /// A note was judged as bad or missed
+    None,
+}
+
+/// How many hits of a grade were early or late
+#[derive(Serialize, Deserialize, Clone, Copy, Default, Debug)]
+pub struct TimingCount {
+    /// Hits before the time of the note
+    pub early: u32,
+
+    /// Hits after the time of the note
+    pub late: u32,
 }
 
 impl Default for ChartStatistics {
@@ -24,10 +154,144 @@ impl Default for ChartStatistics {
             max_combo: 0,
             score: 0.0,
             accurate: 0.0,
+            perfect_timing: TimingCount::default(),
+            good_timing: TimingCount::default(),
+            bad_timing: TimingCount::default(),
+            mean_offset: 0.0,
+            offset_std_dev: 0.0,
+            rank: Rank::F,
+            combo_status: ComboStatus::AllPerfect,
+            failed_at: None,
         }
     }
 }
 
+impl Rank {
+    /// Get the rank of a score
+    #[must_use]
+    pub fn from_score(score: f64) -> Rank {
+        match score {
+            1_000_000.0.. => Rank::Phi,
+            960_000.0.. => Rank::V,
+            920_000.0.. => Rank::S,
+            880_000.0.. => Rank::A,
+            820_000.0.. => Rank::B,
+            700_000.0.. => Rank::C,
+            _ => Rank::F,
+        }
+    }
+}
+
+impl std::fmt::Display for Rank {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let letter = match self {
+            Rank::Phi => "φ",
+            Rank::V => "V",
+            Rank::S => "S",
+            Rank::A => "A",
+            Rank::B => "B",
+            Rank::C => "C",
+            Rank::F => "F",
+        };
+        f.write_str(letter)
+    }
+}
+
+impl ComboStatus {
+    /// The tint of the judge lines in the official game, gold while all
+    /// perfect, blue while full combo and white otherwise
+    #[must_use]
+    pub fn line_color(self) -> [u8; 3] {
+        match self {
+            ComboStatus::AllPerfect => [0xfe, 0xff, 0xa9],
+            ComboStatus::FullCombo => [0xa2, 0xee, 0xff],
+            ComboStatus::None => [0xff, 0xff, 0xff],
+        }
+    }
+
+    fn from_counts(good: u32, broken: u32) -> ComboStatus {
+        if broken > 0 {
+            ComboStatus::None
+        } else if good > 0 {
+            ComboStatus::FullCombo
+        } else {
+            ComboStatus::AllPerfect
+        }
+    }
+}
+
+impl TimingCount {
+    fn count(&mut self, offset: f64) {
+        if offset < 0.0 {
+            self.early += 1;
+        } else if offset > 0.0 {
+            self.late += 1;
+        }
+    }
+}
+
+impl Engine {
+    /// Set the order in which judged notes build up the combo, the statistics
+    /// are recomputed
+    pub fn set_combo_order(&mut self, order: ComboOrder) {
+        self.record(ReplayEvent::SetComboOrder(order));
+        self.combo_order = order;
+        refresh_chart_statistics(self);
+    }
+
+    /// Get the order in which judged notes build up the combo
+    #[must_use]
+    pub fn combo_order(&self) -> ComboOrder {
+        self.combo_order
+    }
+
+    /// Get the statistics of the current play
+    #[must_use]
+    pub fn chart_statistics(&self) -> &ChartStatistics {
+        &self.chart_statistics
+    }
+
+    /// Get the offsets of every hit tap and hold in milliseconds, ordered by
+    /// the time of the notes. Negative offsets are early.
+    #[must_use]
+    pub fn hit_offsets(&self) -> Vec<f64> {
+        self.flatten_note_index
+            .iter()
+            .filter_map(|it| it.index(&self.line_states))
+            .filter_map(hit_offset)
+            .collect()
+    }
+}
+
+/// Set the order in which judged notes build up the combo
+pub fn set_combo_order(order: ComboOrder) {
+    ENGINE.with_borrow_mut(|it| it.set_combo_order(order));
+}
+
+/// Get the statistics of the current play
+#[must_use]
+pub fn get_chart_statistics() -> ChartStatistics {
+    ENGINE.with_borrow(|it| it.chart_statistics.clone())
+}
+
+/// Get the offsets of every hit tap and hold in milliseconds, ordered by the
+/// time of the notes. Negative offsets are early.
+#[must_use]
+pub fn get_hit_offsets() -> Vec<f64> {
+    ENGINE.with_borrow(Engine::hit_offsets)
+}
+
+/// The offset of a note that was hit, a missed note, a drag or a flick has
+/// none
+fn hit_offset(state: &NoteState) -> Option<f64> {
+    match state.score {
+        states::NoteScore::Perfect | states::NoteScore::Good | states::NoteScore::Bad => {
+            state.hit_offset
+        }
+        states::NoteScore::Miss | states::NoteScore::None | states::NoteScore::Excluded => None,
+    }
+}
+
 impl NoteIndex {
     pub fn index<'a>(&self, line_states: &'a [LineState]) -> Option<&'a NoteState> {
         line_states
@@ -43,12 +307,23 @@ impl NoteIndex {
     }
 }
 
-pub fn init_flatten_line_state() {
-    LINE_STATES.with_borrow(|line_state| {
-        FLATTEN_NOTE_INDEX.with_borrow_mut(|flatten_index| {
-            internal_init_flatten_line_state(line_state.as_ref(), flatten_index);
-        });
-    });
+pub fn init_flatten_line_state(engine: &mut Engine) {
+    internal_init_flatten_line_state(&engine.line_states, &mut engine.flatten_note_index);
+    let mut positions = engine
+        .line_states
+        .iter()
+        .map(|it| {
+            [
+                vec![0; it.notes_above_state.len()],
+                vec![0; it.notes_below_state.len()],
+            ]
+        })
+        .collect::<Vec<_>>();
+    for (position, it) in engine.flatten_note_index.iter().enumerate() {
+        positions[it.index_in_line][usize::from(!it.above)][it.index_in_notes] = position;
+    }
+    engine.statistics_tally.positions = positions;
+    refresh_chart_statistics(engine);
 }
 
 fn internal_init_flatten_line_state(line_state: &[LineState], flatten_index: &mut Vec<NoteIndex>) {
@@ -83,61 +358,298 @@ fn internal_init_flatten_line_state(line_state: &[LineState], flatten_index: &mu
     *flatten_index = o;
 }
 
-pub(crate) fn refresh_chart_statistics() {
-    LINE_STATES.with_borrow(|line_states| {
-        FLATTEN_NOTE_INDEX.with_borrow(|flatten_index| {
-            CHART_STATISTICS.with_borrow_mut(|chart_statistics| {
-                internal_refresh_chart_statistics(line_states, flatten_index, chart_statistics);
-            });
-        });
-    });
+/// Recompute the statistics and the running totals from every note
+pub(crate) fn refresh_chart_statistics(engine: &mut Engine) {
+    internal_refresh_chart_statistics(
+        &engine.line_states,
+        &engine.flatten_note_index,
+        engine.combo_order,
+        &mut engine.chart_statistics,
+    );
+    let tally = &mut engine.statistics_tally;
+    *tally = StatisticsTally {
+        positions: std::mem::take(&mut tally.positions),
+        ..StatisticsTally::default()
+    };
+    if engine.combo_order == ComboOrder::Judgment {
+        tally.prefix_combo = (
+            engine.chart_statistics.combo,
+            engine.chart_statistics.max_combo,
+        );
+    }
+    let mut states = engine
+        .flatten_note_index
+        .iter()
+        .filter_map(|it| it.index(&engine.line_states))
+        .collect::<Vec<_>>();
+    // the offsets are tallied in the order they were judged, so a restored
+    // play keeps the exact sums of the running totals
+    states.sort_by_key(|it| it.judge_sequence);
+    for state in states {
+        if state.score != NoteScore::Excluded {
+            tally.total_notes += 1;
+        }
+        if let Some(sequence) = state.judge_sequence {
+            tally.next_sequence = tally.next_sequence.max(sequence + 1);
+        }
+        tally_note(tally, state);
+    }
+    if tally.offset_count > 0 {
+        engine.chart_statistics.mean_offset = tally.offset_mean;
+        engine.chart_statistics.offset_std_dev =
+            (tally.offset_m2 / f64::from(tally.offset_count)).sqrt();
+    }
+    if engine.combo_order == ComboOrder::Judgment {
+        return;
+    }
+    advance_cursor(tally, &engine.line_states, &engine.flatten_note_index);
+    tally.pending = (tally.cursor..engine.flatten_note_index.len())
+        .filter(|it| {
+            engine.flatten_note_index[*it]
+                .index(&engine.line_states)
+                .is_some_and(|it| it.score != NoteScore::None)
+        })
+        .collect();
+}
+
+/// Update the statistics with the notes judged by the events, without a full
+/// rescan
+pub(crate) fn count_judge_events(engine: &mut Engine, events_from: usize) {
+    let Engine {
+        line_states,
+        flatten_note_index,
+        chart_statistics,
+        statistics_tally: tally,
+        judge_events,
+        combo_order,
+        play_mode,
+        ..
+    } = engine;
+    let mut judged = judge_events
+        .iter()
+        .skip(events_from)
+        .filter(|it| {
+            matches!(
+                it.kind,
+                JudgeEventKind::Hit | JudgeEventKind::Miss | JudgeEventKind::HoldBreak
+            )
+        })
+        .map(|it| {
+            (
+                tally.positions[it.line][usize::from(!it.above)][it.note_index],
+                it.time,
+            )
+        })
+        .collect::<Vec<_>>();
+    // notes judged in the same tick are ordered by their end time
+    judged.sort_unstable_by_key(|it| it.0);
+    for (position, time) in judged {
+        let index = &flatten_note_index[position];
+        let state = &mut line_states[index.index_in_line];
+        let state = if index.above {
+            &mut state.notes_above_state[index.index_in_notes]
+        } else {
+            &mut state.notes_below_state[index.index_in_notes]
+        };
+        state.judge_sequence = Some(tally.next_sequence);
+        tally.next_sequence += 1;
+        tally_note(tally, state);
+        if chart_statistics.failed_at.is_none() && play_mode.fails(state.score) {
+            chart_statistics.failed_at = Some(time);
+        }
+        if let Some(offset) = hit_offset(state) {
+            match state.score {
+                NoteScore::Perfect => chart_statistics.perfect_timing.count(offset),
+                NoteScore::Good => chart_statistics.good_timing.count(offset),
+                _ => chart_statistics.bad_timing.count(offset),
+            }
+        }
+        match combo_order {
+            ComboOrder::Judgment => {
+                tally.prefix_combo = fold_combo(tally.prefix_combo, state.score);
+            }
+            ComboOrder::EndTime => {
+                tally.pending.insert(position);
+            }
+        }
+    }
+    let (combo, max_combo) = match combo_order {
+        ComboOrder::Judgment => tally.prefix_combo,
+        ComboOrder::EndTime => {
+            advance_cursor(tally, line_states, flatten_note_index);
+            tally
+                .pending
+                .iter()
+                .filter_map(|it| flatten_note_index[*it].index(line_states))
+                .fold(tally.prefix_combo, |combo, it| fold_combo(combo, it.score))
+        }
+    };
+    let total_notes = f64::from(tally.total_notes.max(1));
+    let accurate = (f64::from(tally.perfect) + f64::from(tally.good) * 0.65) / total_notes;
+    chart_statistics.combo = combo;
+    chart_statistics.max_combo = max_combo;
+    chart_statistics.accurate = accurate;
+    chart_statistics.score =
+        (f64::from(max_combo) / total_notes * 100_000.0) + (accurate * 900_000.0);
+    chart_statistics.rank = Rank::from_score(chart_statistics.score);
+    chart_statistics.combo_status = ComboStatus::from_counts(tally.good, tally.broken);
+    if tally.offset_count > 0 {
+        chart_statistics.mean_offset = tally.offset_mean;
+        chart_statistics.offset_std_dev = (tally.offset_m2 / f64::from(tally.offset_count)).sqrt();
+    }
+    debug_assert!(
+        matches_rescan(engine),
+        "incremental statistics differ from a full rescan"
+    );
+}
+
+/// Add a judged note to the counts of the running totals
+fn tally_note(tally: &mut StatisticsTally, state: &NoteState) {
+    match state.score {
+        NoteScore::Perfect => tally.perfect += 1,
+        NoteScore::Good => tally.good += 1,
+        NoteScore::Bad | NoteScore::Miss => tally.broken += 1,
+        NoteScore::None | NoteScore::Excluded => {}
+    }
+    if let Some(offset) = hit_offset(state) {
+        tally.offset_count += 1;
+        let delta = offset - tally.offset_mean;
+        tally.offset_mean += delta / f64::from(tally.offset_count);
+        tally.offset_m2 += delta * (offset - tally.offset_mean);
+    }
+}
+
+/// Fold the judged notes at the cursor into the prefix combo
+fn advance_cursor(
+    tally: &mut StatisticsTally,
+    line_states: &[LineState],
+    flatten_index: &[NoteIndex],
+) {
+    while let Some(state) = flatten_index
+        .get(tally.cursor)
+        .and_then(|it| it.index(line_states))
+    {
+        if state.score == NoteScore::None {
+            break;
+        }
+        tally.prefix_combo = fold_combo(tally.prefix_combo, state.score);
+        tally.pending.remove(&tally.cursor);
+        tally.cursor += 1;
+    }
+}
+
+fn fold_combo((combo, max_combo): (u32, u32), score: NoteScore) -> (u32, u32) {
+    match score {
+        NoteScore::Perfect | NoteScore::Good => (combo + 1, max_combo.max(combo + 1)),
+        NoteScore::Bad | NoteScore::Miss => (0, max_combo),
+        NoteScore::None | NoteScore::Excluded => (combo, max_combo),
+    }
+}
+
+/// Whether the incremental statistics agree with a full rescan
+fn matches_rescan(engine: &Engine) -> bool {
+    let mut rescan = ChartStatistics::default();
+    internal_refresh_chart_statistics(
+        &engine.line_states,
+        &engine.flatten_note_index,
+        engine.combo_order,
+        &mut rescan,
+    );
+    let it = &engine.chart_statistics;
+    let close = |a: f64, b: f64| (a - b).abs() <= 1e-6 * a.abs().max(1.0);
+    it.combo == rescan.combo
+        && it.max_combo == rescan.max_combo
+        && it.rank == rescan.rank
+        && it.combo_status == rescan.combo_status
+        && it.score.to_bits() == rescan.score.to_bits()
+        && it.accurate.to_bits() == rescan.accurate.to_bits()
+        && [
+            (it.perfect_timing, rescan.perfect_timing),
+            (it.good_timing, rescan.good_timing),
+            (it.bad_timing, rescan.bad_timing),
+        ]
+        .iter()
+        .all(|(a, b)| a.early == b.early && a.late == b.late)
+        && close(it.mean_offset, rescan.mean_offset)
+        && close(it.offset_std_dev, rescan.offset_std_dev)
 }
 
 fn internal_refresh_chart_statistics(
     line_states: &[LineState],
     flatten_index: &[NoteIndex],
+    combo_order: ComboOrder,
     chart_statistics: &mut ChartStatistics,
 ) {
-    let mut combos = vec![0u32];
-    for it in flatten_index {
-        let state = it.index(line_states);
-        match state {
-            None => {}
-            Some(state) => match state.score {
-                states::NoteScore::Perfect | states::NoteScore::Good => {
-                    if let Some(it) = combos.last_mut() {
-                        *it += 1;
-                    }
-                }
-                states::NoteScore::Bad | states::NoteScore::Miss => {
-                    combos.push(0u32);
-                }
-                states::NoteScore::None => {}
-            },
-        }
+    let mut ordered = flatten_index
+        .iter()
+        .filter_map(|it| it.index(line_states))
+        .collect::<Vec<_>>();
+    if combo_order == ComboOrder::Judgment {
+        // notes judged by seeking have no sequence and come first
+        ordered.sort_by_key(|it| it.judge_sequence);
     }
-    let max_combo = combos.iter().max().copied().unwrap_or(0u32);
-    let current_combo = combos.last().copied().unwrap_or(0u32);
+    let (current_combo, max_combo) = ordered
+        .iter()
+        .fold((0, 0), |combo, it| fold_combo(combo, it.score));
     let judge_results =
         flatten_index
             .iter()
-            .fold((0, 0), |score, it| match it.index(line_states) {
+            .fold((0, 0, 0), |score, it| match it.index(line_states) {
                 None => score,
                 Some(state) => match state.score {
-                    states::NoteScore::Perfect => (score.0 + 1, score.1),
-                    states::NoteScore::Good => (score.0, score.1 + 1),
+                    states::NoteScore::Perfect => (score.0 + 1, score.1, score.2),
+                    states::NoteScore::Good => (score.0, score.1 + 1, score.2),
+                    states::NoteScore::Bad | states::NoteScore::Miss => {
+                        (score.0, score.1, score.2 + 1)
+                    }
                     _ => score,
                 },
             });
-    let total_notes = flatten_index.len();
+    let total_notes = flatten_index
+        .iter()
+        .filter_map(|it| it.index(line_states))
+        .filter(|it| it.score != states::NoteScore::Excluded)
+        .count()
+        .max(1);
     let accurate = (f64::from(judge_results.0) + f64::from(judge_results.1) * 0.65)
         / f64::from(total_notes as u32);
     let score =
         (f64::from(max_combo) / f64::from(total_notes as u32) * 100_000.0) + (accurate * 900_000.0);
+    let mut perfect_timing = TimingCount::default();
+    let mut good_timing = TimingCount::default();
+    let mut bad_timing = TimingCount::default();
+    let mut offsets = Vec::new();
+    for state in flatten_index.iter().filter_map(|it| it.index(line_states)) {
+        let Some(offset) = hit_offset(state) else {
+            continue;
+        };
+        match state.score {
+            states::NoteScore::Perfect => perfect_timing.count(offset),
+            states::NoteScore::Good => good_timing.count(offset),
+            _ => bad_timing.count(offset),
+        }
+        offsets.push(offset);
+    }
+    let (mean_offset, offset_std_dev) = if offsets.is_empty() {
+        (0.0, 0.0)
+    } else {
+        let count = f64::from(offsets.len() as u32);
+        let mean = offsets.iter().sum::<f64>() / count;
+        let variance = offsets.iter().map(|it| (it - mean).powi(2)).sum::<f64>() / count;
+        (mean, variance.sqrt())
+    };
     *chart_statistics = ChartStatistics {
         combo: current_combo,
         max_combo,
         score,
         accurate,
+        perfect_timing,
+        good_timing,
+        bad_timing,
+        mean_offset,
+        offset_std_dev,
+        rank: Rank::from_score(score),
+        combo_status: ComboStatus::from_counts(judge_results.1, judge_results.2),
+        failed_at: chart_statistics.failed_at,
     };
 }
@@ -0,0 +1,96 @@
+mod common;
+
+use common::{TAP, line_json, lines_json};
+use phasetida_core::ChartRaw;
+use serde_json::Value;
+
+/// A `PhiEdit` chart with one line, two taps above it and a hold below it
+const PEC: &str = "0
+bp 0 120
+cv 0 0 7
+cp 0 0 1024 700
+cd 0 0 0
+ca 0 0 255
+n1 0 1 0 1 0
+# 1
+& 1
+n1 0 2 512 1 0
+# 1
+& 1
+n2 0 3 4 -512 2 0
+# 1
+& 1
+";
+
+fn export(chart: ChartRaw) -> Value {
+    serde_json::from_str(&chart.convert_to_v3().to_json().unwrap()).unwrap()
+}
+
+#[test]
+fn exported_chart_has_the_official_fields() {
+    let chart = export(ChartRaw::from_pec(PEC).unwrap());
+    assert_eq!(chart["formatVersion"], 3);
+    assert_eq!(chart["numOfNotes"], 3);
+    let line = &chart["judgeLineList"][0];
+    assert_eq!(line["numOfNotes"], 3);
+    assert_eq!(line["numOfNotesAbove"], 2);
+    assert_eq!(line["numOfNotesBelow"], 1);
+    for events in ["judgeLineRotateEvents", "judgeLineDisappearEvents"] {
+        for event in line[events].as_array().unwrap() {
+            assert_eq!(event["start2"], 0.0, "{events}");
+            assert_eq!(event["end2"], 0.0, "{events}");
+        }
+    }
+}
+
+#[test]
+fn exported_chart_loads_again() {
+    for chart in [
+        ChartRaw::from_pec(PEC).unwrap(),
+        ChartRaw::from_json(
+            r#"{"formatVersion":1,"offset":0,"judgeLineList":[{"bpm":120,"notesAbove":[{"type":1,"time":32,"positionX":0,"holdTime":0,"speed":1,"floorPosition":0.5}],"notesBelow":[],"speedEvents":[{"startTime":0,"endTime":1e9,"value":1}],"judgeLineMoveEvents":[{"startTime":-99999,"endTime":1e9,"start":440260,"end":440260}],"judgeLineRotateEvents":[{"startTime":-99999,"endTime":1e9,"start":0,"end":0}],"judgeLineDisappearEvents":[{"startTime":-99999,"endTime":1e9,"start":1,"end":1}]}]}"#,
+        )
+        .unwrap(),
+    ] {
+        let exported = export(chart);
+        let loaded = ChartRaw::from_value(exported.clone()).unwrap();
+        assert_eq!(loaded.format_version(), 3);
+        assert_eq!(export(loaded), exported);
+    }
+}
+
+#[test]
+fn exported_speed_events_have_their_floor_position() {
+    // at 120 BPM, 32 ticks at a speed of 1 and 32 ticks at a speed of 2 move
+    // the floor by half a unit and one unit
+    let speeds = [(0.0, 32.0, 1.0), (32.0, 64.0, 2.0), (64.0, 1e9, 0.5)];
+    let official = lines_json(&[line_json(&[(TAP, 96, 0.0, 0.0)], &speeds)])
+        .replace(
+            r#""endTime":32,"value":1}"#,
+            r#""endTime":32,"value":1,"floorPosition":0}"#,
+        )
+        .replace(
+            r#""endTime":64,"value":2}"#,
+            r#""endTime":64,"value":2,"floorPosition":0.5}"#,
+        )
+        .replace(
+            r#""endTime":1000000000,"value":0.5}"#,
+            r#""endTime":1000000000,"value":0.5,"floorPosition":1.5}"#,
+        );
+    let official: Value = serde_json::from_str(&official).unwrap();
+    let exported = export(ChartRaw::from_value(official.clone()).unwrap());
+    let floor_positions = |chart: &Value| {
+        chart["judgeLineList"][0]["speedEvents"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|it| it["floorPosition"].as_f64().unwrap())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(floor_positions(&official), [0.0, 0.5, 1.5]);
+    assert_eq!(floor_positions(&exported), floor_positions(&official));
+    assert_eq!(
+        exported["judgeLineList"][0]["notesAbove"][0]["floorPosition"],
+        1.75
+    );
+}
@@ -0,0 +1,189 @@
+use std::f64::consts::PI;
+
+use phasetida_core::{Chart, ChartLoadError, ChartRaw};
+
+fn convert(text: &str) -> Chart {
+    let chart = ChartRaw::from_pec(text).unwrap();
+    assert_eq!(chart.format_version(), 0);
+    let (chart, report) = chart.convert_to_v3_with_report();
+    assert!(report.issues.is_empty());
+    chart
+}
+
+fn error(text: &str) -> (usize, String) {
+    match ChartRaw::from_pec(text) {
+        Err(ChartLoadError::InvalidPec { line, message }) => (line, message),
+        Err(err) => panic!("{err}"),
+        Ok(_) => panic!("the chart is parsed"),
+    }
+}
+
+/// The value of the events at `tick`, interpolated like the engine
+fn value_at<T>(events: &[T], tick: f64, range: impl Fn(&T) -> (f64, f64, f64, f64)) -> f64 {
+    let (start_time, end_time, start, end) = events
+        .iter()
+        .map(range)
+        .find(|(start_time, end_time, ..)| *start_time <= tick && tick < *end_time)
+        .unwrap();
+    start + (end - start) * (tick - start_time) / (end_time - start_time)
+}
+
+#[test]
+fn minimal_chart_converts_to_the_official_format() {
+    let chart = convert(
+        "150
+bp 0 120
+cv 0 0 5.85
+cp 0 0 1024 700
+cd 0 0 0
+ca 0 0 255
+n1 0 1 -1024 1 0
+# 1
+& 1
+n2 0 2 3 0 2 0
+# 2
+& 1
+",
+    );
+    assert!(chart.offset.abs() < 1e-9);
+    let [line] = chart.judge_line_list.as_slice() else {
+        panic!("{} lines", chart.judge_line_list.len());
+    };
+    assert!((line.bpm - 120.0).abs() < 1e-9);
+    let [tap] = line.notes_above.as_slice() else {
+        panic!("{} notes above", line.notes_above.len());
+    };
+    assert_eq!((i8::from(tap.r#type), tap.time), (1, 32));
+    assert!((tap.position_x + 9.0).abs() < 1e-9);
+    assert!((tap.floor_position - 0.5).abs() < 1e-9);
+    let [hold] = line.notes_below.as_slice() else {
+        panic!("{} notes below", line.notes_below.len());
+    };
+    assert_eq!((i8::from(hold.r#type), hold.time), (3, 64));
+    assert!((hold.hold_time - 32.0).abs() < 1e-9);
+    assert!((hold.speed - 2.0).abs() < 1e-9);
+    let move_events = &line.move_events;
+    let x = value_at(move_events, 32.0, |it| {
+        (it.start_time, it.end_time, it.start, it.end)
+    });
+    let y = value_at(move_events, 32.0, |it| {
+        (it.start_time, it.end_time, it.start2, it.end2)
+    });
+    assert!((x - 0.5).abs() < 1e-9 && (y - 0.5).abs() < 1e-9);
+    let alpha = value_at(&line.alpha_events, 32.0, |it| {
+        (it.start_time, it.end_time, it.start, it.end)
+    });
+    assert!((alpha - 1.0).abs() < 1e-9);
+}
+
+#[test]
+fn malformed_line_is_an_error_with_its_line_number() {
+    let header = "0\nbp 0 120\n";
+    assert_eq!(
+        error(&format!("{header}n1 0 x 0 1 0\n")),
+        (3, "invalid number `x` in `n1`".to_string())
+    );
+    assert_eq!(
+        error(&format!("{header}cv 0 0 1\nzz 0\n")),
+        (4, "unknown command `zz`".to_string())
+    );
+    assert_eq!(
+        error(&format!("{header}# 1\n")),
+        (3, "`#` without a note".to_string())
+    );
+    assert_eq!(
+        error(&format!("{header}cm 0 1 2 0 0 1.5\n")),
+        (3, "invalid integer `1.5` in `cm`".to_string())
+    );
+    assert_eq!(
+        error(&format!("{header}n2 0 1 2\n")),
+        (3, "unexpected end of file in `n2`".to_string())
+    );
+    assert_eq!(
+        error("0\n\nbp 0 -120\n"),
+        (3, "non-positive bpm: -120".to_string())
+    );
+    assert_eq!(
+        error("0\nn1 0 1 0 1 0\n"),
+        (2, "missing `bp` command".to_string())
+    );
+}
+
+#[test]
+fn motions_ease_from_the_value_at_their_start() {
+    let chart = convert(
+        "0
+bp 0 120
+cv 0 0 5.85
+cp 0 0 0 0
+cm 0 1 2 2048 1400 5
+cd 0 0 0
+cr 0 1 2 90 2
+ca 0 0 0
+cf 0 1 2 255
+",
+    );
+    let line = &chart.judge_line_list[0];
+    let x = |tick| {
+        value_at(&line.move_events, tick, |it| {
+            (it.start_time, it.end_time, it.start, it.end)
+        })
+    };
+    let y = |tick| {
+        value_at(&line.move_events, tick, |it| {
+            (it.start_time, it.end_time, it.start2, it.end2)
+        })
+    };
+    let rotate = |tick| {
+        value_at(&line.rotate_events, tick, |it| {
+            (it.start_time, it.end_time, it.start, it.end)
+        })
+    };
+    let alpha = |tick| {
+        value_at(&line.alpha_events, tick, |it| {
+            (it.start_time, it.end_time, it.start, it.end)
+        })
+    };
+    for (tick, progress) in [(32.0, 0.0), (48.0, 0.5), (64.0, 1.0), (96.0, 1.0)] {
+        let quad_in: f64 = progress * progress;
+        assert!((x(tick) - quad_in).abs() < 1e-9, "x at {tick}");
+        assert!((y(tick) - quad_in).abs() < 1e-9, "y at {tick}");
+        let sine_out = (progress * PI / 2.0).sin();
+        assert!(
+            (rotate(tick) + 90.0 * sine_out).abs() < 1e-9,
+            "rotate at {tick}"
+        );
+        assert!((alpha(tick) - progress).abs() < 1e-9, "alpha at {tick}");
+    }
+}
+
+#[test]
+fn speed_events_set_the_floor_position_of_notes() {
+    let chart = convert(
+        "0
+bp 0 120
+cv 0 0 5.85
+cv 0 2 11.7
+n1 0 1 0 1 0
+n1 0 2 0 1 0
+n1 0 3 0 1 0
+",
+    );
+    let line = &chart.judge_line_list[0];
+    let speed = |tick| {
+        value_at(&line.speed_events, tick, |it| {
+            (it.start_time, it.end_time, it.value, it.value)
+        })
+    };
+    assert!((speed(32.0) - 1.0).abs() < 1e-9);
+    assert!((speed(96.0) - 2.0).abs() < 1e-9);
+    let floor_positions = line
+        .notes_above
+        .iter()
+        .map(|it| it.floor_position)
+        .collect::<Vec<_>>();
+    assert_eq!(floor_positions.len(), 3);
+    for (actual, expected) in floor_positions.iter().zip([0.5, 1.0, 2.0]) {
+        assert!((actual - expected).abs() < 1e-9, "{floor_positions:?}");
+    }
+}
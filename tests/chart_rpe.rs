@@ -0,0 +1,286 @@
+use std::f64::consts::PI;
+
+use phasetida_core::{Chart, ChartRaw, ConversionReport, Engine};
+use serde_json::{Value, json};
+
+/// An event of Re:PhiEdit from `start` to `end` between two beats
+fn event(beats: (i32, i32), start: f64, end: f64, easing_type: i32) -> Value {
+    json!({
+        "startTime": [beats.0, 0, 1],
+        "endTime": [beats.1, 0, 1],
+        "start": start,
+        "end": end,
+        "easingType": easing_type,
+    })
+}
+
+/// A line of Re:PhiEdit at the center of the screen, with a speed of 9 RPE
+/// units, which is 2 in the official format
+fn line(notes: Vec<Value>) -> Value {
+    json!({
+        "eventLayers": [{
+            "moveXEvents": [event((0, 8), 0.0, 0.0, 1)],
+            "moveYEvents": [event((0, 8), 0.0, 0.0, 1)],
+            "rotateEvents": [event((0, 8), 0.0, 0.0, 1)],
+            "alphaEvents": [event((0, 8), 255.0, 255.0, 1)],
+            "speedEvents": [event((0, 8), 9.0, 9.0, 1)],
+        }],
+        "notes": notes,
+    })
+}
+
+/// A note of Re:PhiEdit between two beats
+fn note(note_type: i32, beats: (i32, i32), position_x: f64) -> Value {
+    json!({
+        "type": note_type,
+        "startTime": [beats.0, 0, 1],
+        "endTime": [beats.1, 0, 1],
+        "positionX": position_x,
+    })
+}
+
+/// A chart of Re:PhiEdit at 120 BPM, so one beat is 32 ticks
+fn chart(lines: Vec<Value>) -> Value {
+    json!({
+        "META": {"RPEVersion": 150, "offset": 250},
+        "BPMList": [{"bpm": 120, "startTime": [0, 0, 1]}],
+        "judgeLineList": lines,
+    })
+}
+
+fn convert(chart: Value) -> (Chart, ConversionReport) {
+    let chart = ChartRaw::from_value(chart).unwrap();
+    assert_eq!(chart.format_version(), 150);
+    chart.convert_to_v3_with_report()
+}
+
+fn issues(report: &ConversionReport) -> Vec<(Option<usize>, Option<usize>, &str)> {
+    report
+        .issues
+        .iter()
+        .map(|it| (it.line, it.note, it.message.as_str()))
+        .collect()
+}
+
+#[test]
+fn rpe_chart_converts_to_the_official_format() {
+    let mut below = note(3, (3, 3), 0.0);
+    below["above"] = json!(2);
+    let (chart, report) = convert(chart(vec![line(vec![
+        note(1, (1, 1), 150.0),
+        note(2, (2, 4), -75.0),
+        below,
+        note(4, (4, 4), 0.0),
+    ])]));
+    assert!(report.issues.is_empty());
+    assert!((chart.offset - 0.25).abs() < 1e-9);
+    let line = &chart.judge_line_list[0];
+    assert!((line.bpm - 120.0).abs() < 1e-9);
+    let exported = serde_json::to_value(&chart).unwrap();
+    let notes = |side: &str| -> Vec<(i64, i64, f64, f64, f64)> {
+        exported["judgeLineList"][0][side]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|it| {
+                (
+                    it["type"].as_i64().unwrap(),
+                    it["time"].as_i64().unwrap(),
+                    it["positionX"].as_f64().unwrap(),
+                    it["holdTime"].as_f64().unwrap(),
+                    it["floorPosition"].as_f64().unwrap(),
+                )
+            })
+            .collect()
+    };
+    assert_eq!(
+        notes("notesAbove"),
+        [
+            (1, 32, 2.0, 0.0, 1.0),
+            (3, 64, -1.0, 64.0, 2.0),
+            (2, 128, 0.0, 0.0, 4.0)
+        ]
+    );
+    assert_eq!(notes("notesBelow"), [(4, 96, 0.0, 0.0, 3.0)]);
+    let line = &chart.judge_line_list[0];
+    for event in &line.speed_events {
+        assert!((event.value - 2.0).abs() < 1e-9);
+    }
+    for event in &line.move_events {
+        assert!((event.start - 0.5).abs() < 1e-9 && (event.start2 - 0.5).abs() < 1e-9);
+    }
+    for event in &line.alpha_events {
+        assert!((event.start - 1.0).abs() < 1e-9);
+    }
+}
+
+#[test]
+fn unknown_and_fake_notes_are_dropped_and_reported() {
+    let mut fake = note(1, (2, 2), 0.0);
+    fake["isFake"] = json!(1);
+    let mut faded = note(1, (3, 3), 0.0);
+    faded["alpha"] = json!(128);
+    faded["size"] = json!(2);
+    let chart = chart(vec![line(vec![
+        note(1, (1, 1), 0.0),
+        note(7, (1, 1), 0.0),
+        fake,
+        faded,
+    ])]);
+    let metadata = Engine::new()
+        .try_init(ChartRaw::from_value(chart.clone()).unwrap())
+        .unwrap();
+    assert_eq!(
+        issues(&metadata.conversion_report),
+        [
+            (Some(0), Some(1), "unknown note type 7 is dropped"),
+            (
+                Some(0),
+                Some(2),
+                "fake note is not supported and is dropped"
+            ),
+            (Some(0), Some(3), "alpha, size of the note is ignored"),
+        ]
+    );
+    let (converted, _) = convert(chart);
+    let times = converted.judge_line_list[0]
+        .notes_above
+        .iter()
+        .map(|it| it.time)
+        .collect::<Vec<_>>();
+    assert_eq!(times, [32, 96]);
+}
+
+#[test]
+fn unsupported_line_features_are_reported() {
+    let mut line = line(Vec::new());
+    line["father"] = json!(0);
+    line["bpmfactor"] = json!(2);
+    line["Texture"] = json!("custom.png");
+    line["attachUI"] = json!("pause");
+    line["extended"] = json!({"scaleXEvents": [event((0, 1), 1.0, 2.0, 1)]});
+    let mut bezier = event((1, 2), 90.0, 0.0, 2);
+    bezier["bezier"] = json!(1);
+    line["eventLayers"][0]["rotateEvents"] = json!([event((0, 1), 0.0, 90.0, 99), bezier]);
+    let (_, report) = convert(chart(vec![line]));
+    assert_eq!(
+        issues(&report),
+        [
+            (
+                Some(0),
+                None,
+                "parent line 0 is not supported, the line moves on its own"
+            ),
+            (
+                Some(0),
+                None,
+                "bpm factor 2 is not supported and is ignored"
+            ),
+            (Some(0), None, "custom line texture is not supported"),
+            (Some(0), None, "attaching the line to UI is not supported"),
+            (
+                Some(0),
+                None,
+                "extended events (scale, color, text, incline) are not supported"
+            ),
+            (
+                Some(0),
+                None,
+                "easing type 99 is not supported, linear easing is used instead"
+            ),
+            (
+                Some(0),
+                None,
+                "bezier easing is not supported, the easing type is used instead"
+            ),
+        ]
+    );
+}
+
+/// An easing curve of `x` in `[0, 1]`
+type Curve = fn(f64) -> f64;
+
+/// The rotation of the first line at `tick`, interpolated like the engine
+fn rotation_at(chart: &Chart, tick: f64) -> f64 {
+    let event = chart.judge_line_list[0]
+        .rotate_events
+        .iter()
+        .find(|it| it.start_time <= tick && tick < it.end_time)
+        .unwrap();
+    let percent = (tick - event.start_time) / (event.end_time - event.start_time);
+    event.start + (event.end - event.start) * percent
+}
+
+/// A line rotating from 0 to 100 degrees in the first beat
+fn eased(easing_type: i32, left: f64, right: f64) -> (Chart, ConversionReport) {
+    let mut line = line(Vec::new());
+    let mut rotate = event((0, 1), 0.0, -100.0, easing_type);
+    rotate["easingLeft"] = json!(left);
+    rotate["easingRight"] = json!(right);
+    line["eventLayers"][0]["rotateEvents"] = json!([rotate]);
+    convert(chart(vec![line]))
+}
+
+#[test]
+fn easing_curves_match_re_phi_edit() {
+    let curves: [(i32, Curve); 16] = [
+        (1, |x| x),
+        (2, |x| (x * PI / 2.0).sin()),
+        (3, |x| 1.0 - (x * PI / 2.0).cos()),
+        (4, |x| 1.0 - (1.0 - x).powi(2)),
+        (5, |x| x.powi(2)),
+        (6, |x| (1.0 - (PI * x).cos()) / 2.0),
+        (8, |x| 1.0 - (1.0 - x).powi(3)),
+        (9, |x| x.powi(3)),
+        (10, |x| 1.0 - (1.0 - x).powi(4)),
+        (11, |x| x.powi(4)),
+        (14, |x| 1.0 - (1.0 - x).powi(5)),
+        (15, |x| x.powi(5)),
+        (16, |x| 1.0 - 2f64.powf(-10.0 * x)),
+        (17, |x| 2f64.powf(10.0 * x - 10.0)),
+        (18, |x| (1.0 - (x - 1.0).powi(2)).sqrt()),
+        (19, |x| 1.0 - (1.0 - x.powi(2)).sqrt()),
+    ];
+    for (easing_type, curve) in curves {
+        let (chart, _) = eased(easing_type, 0.0, 1.0);
+        for x in [0.25, 0.5, 0.75] {
+            let rotation = rotation_at(&chart, x * 32.0);
+            let expected = 100.0 * curve(x);
+            assert!(
+                (rotation - expected).abs() < 1e-6,
+                "easing {easing_type} at {x}: {rotation} != {expected}"
+            );
+        }
+    }
+    let (bounce, _) = eased(26, 0.0, 1.0);
+    assert!((rotation_at(&bounce, 16.0) - 76.5625).abs() < 1e-6);
+}
+
+#[test]
+fn every_supported_easing_starts_at_0_and_ends_at_1() {
+    for easing_type in 0..=29 {
+        let (chart, report) = eased(easing_type, 0.0, 1.0);
+        assert!(report.issues.is_empty(), "easing {easing_type}");
+        assert!(
+            rotation_at(&chart, 0.0).abs() < 1e-6,
+            "easing {easing_type}"
+        );
+        assert!(
+            (rotation_at(&chart, 32.0) - 100.0).abs() < 1e-6,
+            "easing {easing_type}"
+        );
+    }
+    let (chart, report) = eased(30, 0.0, 1.0);
+    assert_eq!(report.issues.len(), 1);
+    assert!((rotation_at(&chart, 16.0) - 50.0).abs() < 1e-6);
+}
+
+#[test]
+fn clipped_easing_is_rescaled_to_the_event() {
+    // the second half of a quadratic ease in, from 0.25 to 1
+    let (chart, _) = eased(5, 0.5, 1.0);
+    assert!(rotation_at(&chart, 0.0).abs() < 1e-6);
+    let expected = 100.0 * (0.75f64.powi(2) - 0.25) / 0.75;
+    assert!((rotation_at(&chart, 16.0) - expected).abs() < 1e-6);
+    assert!((rotation_at(&chart, 32.0) - 100.0).abs() < 1e-6);
+}
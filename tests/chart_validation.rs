@@ -0,0 +1,132 @@
+mod common;
+
+use common::{HOLD, TAP, chart, chart_json, line_json, lines_json};
+use phasetida_core::{
+    Chart, ChartLoadError, ChartRaw, Diagnostic, DiagnosticTarget, Engine, Severity, validate,
+};
+
+fn summary(diagnostics: &[Diagnostic]) -> Vec<(Severity, Option<usize>, DiagnosticTarget)> {
+    diagnostics
+        .iter()
+        .map(|it| (it.severity, it.line, it.target))
+        .collect()
+}
+
+/// A tap at 1 s and a hold at 1.5 s
+fn valid_chart() -> Chart {
+    chart(&[(TAP, 64, 0.0, 0.0), (HOLD, 96, 1.0, 32.0)]).convert_to_v3()
+}
+
+#[test]
+fn valid_chart_has_no_diagnostics() {
+    assert!(validate(&valid_chart()).is_empty());
+}
+
+#[test]
+fn problems_the_engine_plays_through_are_warnings() {
+    let json = lines_json(&[line_json(
+        &[(HOLD, 32, 0.0, 0.0), (TAP, -8, 12.0, 0.0)],
+        &[(64.0, 1e9, 1.0), (0.0, 64.0, 1.0), (96.0, 80.0, 1.0)],
+    )]);
+    let metadata = Engine::new()
+        .try_init(ChartRaw::from_json(&json).unwrap())
+        .unwrap();
+    assert_eq!(
+        summary(&metadata.diagnostics),
+        [
+            (Severity::Warning, Some(0), DiagnosticTarget::SpeedEvent(1)),
+            (Severity::Warning, Some(0), DiagnosticTarget::SpeedEvent(2)),
+            (Severity::Warning, Some(0), DiagnosticTarget::SpeedEvent(2)),
+            (Severity::Warning, Some(0), DiagnosticTarget::NoteAbove(0)),
+            (Severity::Warning, Some(0), DiagnosticTarget::NoteAbove(1)),
+            (Severity::Warning, Some(0), DiagnosticTarget::NoteAbove(1)),
+        ]
+    );
+    assert_eq!(
+        metadata.diagnostics[3].to_string(),
+        "warning at line 0, notesAbove[0]: hold note has a non-positive hold time: 0"
+    );
+}
+
+#[test]
+fn gaps_and_overlaps_between_events_are_warnings() {
+    let mut chart = valid_chart();
+    let events = &mut chart.judge_line_list[0].alpha_events;
+    let mut event = events[0].clone();
+    event.start_time = 1e9 - 10.0;
+    event.end_time = 2e9;
+    events.push(event.clone());
+    event.start_time = 3e9;
+    event.end_time = 4e9;
+    events.push(event);
+    assert_eq!(
+        summary(&validate(&chart)),
+        [
+            (Severity::Warning, Some(0), DiagnosticTarget::AlphaEvent(1)),
+            (Severity::Warning, Some(0), DiagnosticTarget::AlphaEvent(2)),
+        ]
+    );
+}
+
+#[test]
+fn suspicious_floor_position_is_a_warning() {
+    let mut chart = valid_chart();
+    chart.judge_line_list[0].notes_above[1].floor_position = 3.0;
+    let diagnostics = validate(&chart);
+    assert_eq!(
+        summary(&diagnostics),
+        [(Severity::Warning, Some(0), DiagnosticTarget::NoteAbove(1))]
+    );
+    assert!(diagnostics[0].message.contains("1.5"), "{}", diagnostics[0]);
+}
+
+#[test]
+fn values_the_engine_can_not_compute_with_are_errors() {
+    let mut chart = valid_chart();
+    chart.offset = f64::NAN;
+    chart.judge_line_list[0].bpm = 0.0;
+    chart.judge_line_list[0].notes_above[0].position_x = f64::INFINITY;
+    chart.judge_line_list[0].move_events[0].start = f64::NAN;
+    let diagnostics = validate(&chart);
+    assert_eq!(
+        summary(&diagnostics),
+        [
+            (Severity::Error, None, DiagnosticTarget::Chart),
+            (Severity::Error, Some(0), DiagnosticTarget::Line),
+            (Severity::Error, Some(0), DiagnosticTarget::MoveEvent(0)),
+            (Severity::Error, Some(0), DiagnosticTarget::NoteAbove(0)),
+        ]
+    );
+
+    let Err(ChartLoadError::Validation(rejected)) = Engine::new().try_init(ChartRaw::V3(chart))
+    else {
+        panic!("the chart is not rejected");
+    };
+    assert_eq!(summary(&rejected), summary(&diagnostics));
+}
+
+#[test]
+fn recomputed_floor_positions_are_reported() {
+    let json = chart_json(&[(TAP, 64, 0.0, 0.0), (HOLD, 96, 1.0, 32.0)])
+        .replace(r#""floorPosition":1.5"#, r#""floorPosition":3"#);
+    let mut engine = Engine::new();
+    let metadata = engine.init_from_json(&json).unwrap();
+    assert_eq!(metadata.diagnostics.len(), 1);
+    assert!(metadata.floor_position_report.is_none());
+
+    engine.set_recompute_floor_position(true);
+    let metadata = engine.init_from_json(&json).unwrap();
+    assert!(metadata.diagnostics.is_empty());
+    let report = metadata.floor_position_report.unwrap();
+    assert_eq!(report.recomputed, 2);
+    assert!((report.max_delta - 1.5).abs() < 1e-9);
+    let [change] = report.changes.as_slice() else {
+        panic!("{} notes changed", report.changes.len());
+    };
+    assert_eq!(
+        (change.line, change.target),
+        (0, DiagnosticTarget::NoteAbove(1))
+    );
+    assert!((change.stored - 3.0).abs() < 1e-9);
+    assert!((change.computed - 1.5).abs() < 1e-9);
+}
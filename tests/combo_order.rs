@@ -0,0 +1,143 @@
+mod common;
+
+use common::{HOLD, TAP, at, chart, engine, play};
+use phasetida_core::{ChartRaw, ComboOrder, Engine};
+
+fn ordered(chart: ChartRaw, order: ComboOrder) -> Engine {
+    let mut engine = engine(chart);
+    engine.set_combo_order(order);
+    engine
+}
+
+/// A hold at the center from 0.5 s to 1.09 s, held to its end, and a tap far
+/// to the right at 1 s that is missed. The tap ends first, but is judged as
+/// missed only after the hold is complete.
+fn hold_then_missed_tap(order: ComboOrder) -> Engine {
+    let mut engine = ordered(chart(&[(HOLD, 32, 0.0, 38.0), (TAP, 64, 8.0, 0.0)]), order);
+    play(&mut engine, 0.0, 1.5, |engine, time| {
+        if at(time, 0.5) {
+            engine.set_touch_down(0, 960.0, 540.0);
+        }
+    });
+    engine
+}
+
+#[test]
+fn miss_after_hold_breaks_combo_in_judgment_order() {
+    let engine = hold_then_missed_tap(ComboOrder::Judgment);
+    let statistics = engine.chart_statistics();
+    assert_eq!(statistics.combo, 0);
+    assert_eq!(statistics.max_combo, 1);
+}
+
+#[test]
+fn miss_after_hold_keeps_combo_in_end_time_order() {
+    let engine = hold_then_missed_tap(ComboOrder::EndTime);
+    let statistics = engine.chart_statistics();
+    assert_eq!(statistics.combo, 1);
+    assert_eq!(statistics.max_combo, 1);
+}
+
+/// A hold at the center from 0.5 s to 2 s with taps at 1 s and 1.5 s on top
+/// of it. The hold is released at 1.2 s, between the two taps.
+fn hold_broken_between_taps(order: ComboOrder) -> (Engine, Vec<u32>) {
+    let mut engine = ordered(
+        chart(&[
+            (HOLD, 32, 0.0, 96.0),
+            (TAP, 64, 0.0, 0.0),
+            (TAP, 96, 0.0, 0.0),
+        ]),
+        order,
+    );
+    let mut combos = Vec::new();
+    play(&mut engine, 0.0, 2.5, |engine, time| {
+        combos.push(engine.chart_statistics().combo);
+        if at(time, 0.5) {
+            engine.set_touch_down(0, 960.0, 540.0);
+        }
+        if at(time, 1.0) || at(time, 1.5) {
+            engine.set_touch_down(1, 960.0, 540.0);
+        }
+        if at(time, 1.1) || at(time, 1.6) {
+            engine.set_touch_up(1);
+        }
+        if at(time, 1.2) {
+            engine.set_touch_up(0);
+        }
+    });
+    combos.push(engine.chart_statistics().combo);
+    combos.dedup();
+    (engine, combos)
+}
+
+#[test]
+fn broken_hold_resets_combo_when_it_breaks() {
+    let (engine, combos) = hold_broken_between_taps(ComboOrder::Judgment);
+    assert_eq!(combos, [0, 1, 0, 1]);
+    assert_eq!(engine.chart_statistics().max_combo, 1);
+}
+
+/// The broken hold still counts at its end, so the second tap extends the
+/// combo of the first one even though the hold broke in between
+#[test]
+fn broken_hold_resets_combo_at_its_end_in_end_time_order() {
+    let (engine, combos) = hold_broken_between_taps(ComboOrder::EndTime);
+    assert_eq!(combos, [0, 1, 0]);
+    assert_eq!(engine.chart_statistics().max_combo, 2);
+}
+
+#[test]
+fn autoplay_combo_never_drops() {
+    let chart = chart(&[
+        (HOLD, 32, 0.0, 128.0),
+        (TAP, 48, 4.0, 0.0),
+        (HOLD, 64, -4.0, 32.0),
+        (TAP, 80, 2.0, 0.0),
+        (TAP, 112, -2.0, 0.0),
+        (TAP, 176, 0.0, 0.0),
+    ]);
+    for order in [ComboOrder::Judgment, ComboOrder::EndTime] {
+        let mut engine = ordered(chart.clone(), order);
+        let mut last = 0;
+        for tick in 0..=480 {
+            engine.tick_all(f64::from(tick) / 120.0, 1.0 / 120.0, true);
+            let combo = engine.chart_statistics().combo;
+            assert!(
+                combo >= last,
+                "{order:?}: combo dropped from {last} to {combo}"
+            );
+            last = combo;
+        }
+        assert_eq!(last, 6);
+        assert_eq!(engine.chart_statistics().max_combo, 6);
+    }
+}
+
+#[test]
+fn restore_keeps_judgment_order() {
+    let chart = || {
+        chart(&[
+            (HOLD, 32, 0.0, 38.0),
+            (TAP, 64, 8.0, 0.0),
+            (TAP, 112, 0.0, 0.0),
+        ])
+    };
+    let input = |engine: &mut Engine, time: f64| {
+        if at(time, 0.5) || at(time, 1.75) {
+            engine.set_touch_down(0, 960.0, 540.0);
+        }
+    };
+    let mut expected = ordered(chart(), ComboOrder::Judgment);
+    play(&mut expected, 0.0, 2.5, input);
+
+    let mut first = ordered(chart(), ComboOrder::Judgment);
+    play(&mut first, 0.0, 1.5, input);
+    let mut resumed = ordered(chart(), ComboOrder::Judgment);
+    resumed.restore(&first.snapshot());
+    play(&mut resumed, 1.5 + 1.0 / 120.0, 2.5, input);
+
+    let (expected, resumed) = (expected.chart_statistics(), resumed.chart_statistics());
+    assert_eq!(resumed.combo, expected.combo);
+    assert_eq!(resumed.max_combo, expected.max_combo);
+    assert_eq!(expected.combo, 1);
+}
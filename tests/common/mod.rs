@@ -0,0 +1,202 @@
+//! The chart fixture and the helpers shared by the integration tests
+
+#![allow(dead_code)]
+
+use phasetida_core::{BufferWithCursor, ChartRaw, Engine, RENDER_SCHEMA};
+
+pub const TAP: i32 = 1;
+pub const DRAG: i32 = 2;
+pub const HOLD: i32 = 3;
+pub const FLICK: i32 = 4;
+
+/// The time between two ticks of `play`
+pub const DELTA: f64 = 1.0 / 120.0;
+
+/// A note as type, time in ticks, position x and hold time in ticks
+pub type Note = (i32, i32, f64, f64);
+
+/// A speed event as start time, end time and value, times in ticks
+pub type Speed = (f64, f64, f64);
+
+/// A speed of 1 for the whole chart
+pub const CONSTANT_SPEED: &[Speed] = &[(0.0, 1e9, 1.0)];
+
+/// The floor position of `time` ticks, in screen heights
+fn floor_position(time: i32, speeds: &[Speed]) -> f64 {
+    speeds
+        .iter()
+        .map(|(start, end, value)| value * (f64::from(time).min(*end) - start).max(0.0) / 64.0)
+        .sum()
+}
+
+/// A static horizontal line at the center of the screen, at 120 BPM so 64
+/// ticks are one second, with `notes` above it
+pub fn line_json(notes: &[Note], speeds: &[Speed]) -> String {
+    let notes = notes
+        .iter()
+        .map(|(note_type, time, position_x, hold_time)| {
+            format!(
+                r#"{{"type":{note_type},"time":{time},"positionX":{position_x},"holdTime":{hold_time},"speed":1,"floorPosition":{}}}"#,
+                floor_position(*time, speeds)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+    let speeds = speeds
+        .iter()
+        .map(|(start, end, value)| {
+            format!(r#"{{"startTime":{start},"endTime":{end},"value":{value}}}"#)
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+    format!(
+        r#"{{"bpm":120,"notesAbove":[{notes}],"notesBelow":[],"speedEvents":[{speeds}],"judgeLineMoveEvents":[{{"startTime":-99999,"endTime":1e9,"start":0.5,"end":0.5,"start2":0.5,"end2":0.5}}],"judgeLineRotateEvents":[{{"startTime":-99999,"endTime":1e9,"start":0,"end":0}}],"judgeLineDisappearEvents":[{{"startTime":-99999,"endTime":1e9,"start":1,"end":1}}]}}"#
+    )
+}
+
+/// An official v3 chart with the given lines
+pub fn lines_json(lines: &[String]) -> String {
+    format!(
+        r#"{{"formatVersion":3,"offset":0,"judgeLineList":[{}]}}"#,
+        lines.join(",")
+    )
+}
+
+/// A chart with a single line of `line_json` at a constant speed
+pub fn chart_json(notes: &[Note]) -> String {
+    lines_json(&[line_json(notes, CONSTANT_SPEED)])
+}
+
+pub fn chart(notes: &[Note]) -> ChartRaw {
+    ChartRaw::from_json(&chart_json(notes)).unwrap()
+}
+
+pub fn engine(chart: ChartRaw) -> Engine {
+    let mut engine = Engine::new();
+    engine.try_init(chart).unwrap();
+    engine
+}
+
+/// Tick from `from` to `to` seconds, calling `input` before every tick
+pub fn play(engine: &mut Engine, from: f64, to: f64, mut input: impl FnMut(&mut Engine, f64)) {
+    let mut tick = 0;
+    loop {
+        let time = from + f64::from(tick) * DELTA;
+        if time > to {
+            break;
+        }
+        input(engine, time);
+        engine.tick_all(time, DELTA, false);
+        tick += 1;
+    }
+}
+
+/// Tick with autoplay from 0 to `to` seconds
+pub fn autoplay(engine: &mut Engine, to: f64) {
+    let mut tick = 0;
+    while f64::from(tick) * DELTA <= to {
+        engine.tick_all(f64::from(tick) * DELTA, DELTA, true);
+        tick += 1;
+    }
+}
+
+/// Whether `time` is the first tick of `play` at or after `at`
+pub fn at(time: f64, at: f64) -> bool {
+    (at..at + DELTA).contains(&time)
+}
+
+/// A buffer that keeps everything written to it
+pub struct VecBuffer(pub Vec<u8>);
+
+impl BufferWithCursor for VecBuffer {
+    fn write(&mut self, slice: &[u8]) {
+        self.0.extend_from_slice(slice);
+    }
+}
+
+/// The bytes of the current frame of `engine`
+pub fn encode(engine: &Engine) -> Vec<u8> {
+    let mut buffer = VecBuffer(Vec::new());
+    engine.process_state_to_drawable(&mut buffer);
+    buffer.0
+}
+
+/// A field of `RENDER_SCHEMA` as name, type and length
+pub type Field<'a> = (String, &'a str, usize);
+
+/// A record of `RENDER_SCHEMA` as type, name and fields
+pub type Record = (u8, &'static str, Vec<Field<'static>>);
+
+/// The size of a scalar type, named as in C or in `RENDER_SCHEMA`
+pub fn type_size(name: &str) -> usize {
+    match name {
+        "uint8_t" | "int8_t" | "u8" | "i8" => 1,
+        "uint16_t" | "u16" => 2,
+        "uint32_t" | "int32_t" | "float" | "u32" | "i32" | "f32" => 4,
+        "double" | "f64" => 8,
+        _ => panic!("unknown type {name}"),
+    }
+}
+
+/// Split `text` at every `separator` outside of brackets
+fn split_outside_brackets(text: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let (mut depth, mut start) = (0, 0);
+    for (i, it) in text.char_indices() {
+        match it {
+            '[' => depth += 1,
+            ']' => depth -= 1,
+            _ if it == separator && depth == 0 => {
+                parts.push(&text[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&text[start..]);
+    parts
+}
+
+/// The fields of a section of `RENDER_SCHEMA`, written as `name:type`,
+/// `name:[type;len]` or `name[type;len]`
+fn schema_fields(section: &str) -> Vec<Field<'_>> {
+    split_outside_brackets(section, ',')
+        .into_iter()
+        .map(|field| match field.split_once('[') {
+            Some((name, array)) => {
+                let (field_type, len) = array.trim_end_matches(']').split_once(';').unwrap();
+                (
+                    name.trim_end_matches(':').to_string(),
+                    field_type,
+                    len.parse().unwrap(),
+                )
+            }
+            None => {
+                let (name, field_type) = field.split_once(':').unwrap();
+                (name.to_string(), field_type, 1)
+            }
+        })
+        .collect()
+}
+
+/// The size of the fields without padding
+pub fn packed_size(fields: &[Field]) -> usize {
+    fields.iter().map(|(_, it, len)| type_size(it) * len).sum()
+}
+
+/// The header fields of `RENDER_SCHEMA`, and the type, name and fields of
+/// every record except the terminator
+pub fn render_schema() -> (Vec<Field<'static>>, Vec<Record>) {
+    let sections = split_outside_brackets(RENDER_SCHEMA, ';');
+    let header = schema_fields(sections[0].strip_prefix("header:").unwrap());
+    let records = sections[1..]
+        .iter()
+        .filter(|it| !it.starts_with("0:"))
+        .map(|record| {
+            let (record_type, rest) = record.split_once(':').unwrap();
+            let (name, fields) = rest.split_once(':').unwrap();
+            (record_type.parse().unwrap(), name, schema_fields(fields))
+        })
+        .collect();
+    (header, records)
+}
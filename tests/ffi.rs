@@ -0,0 +1,180 @@
+mod common;
+
+use std::collections::{BTreeSet, HashMap};
+
+use common::{TAP, chart_json, packed_size, render_schema, type_size};
+
+use phasetida_core::{
+    ffi::{self, PhasetidaStatus},
+    renders::{RECORD_TYPE_COUNT, RENDER_MAGIC, RENDER_PROTOCOL_VERSION, RenderCommand, decode},
+};
+
+fn load(text: &str) -> PhasetidaStatus {
+    unsafe { ffi::phasetida_load_chart(text.as_ptr(), text.len(), std::ptr::null_mut()) }
+}
+
+#[test]
+fn json_chart_with_byte_order_mark_loads() {
+    let chart = chart_json(&[(TAP, 64, 0.0, 0.0)]);
+    assert_eq!(load(&chart), PhasetidaStatus::Ok);
+    assert_eq!(load(&format!("\u{feff}{chart}")), PhasetidaStatus::Ok);
+    assert_eq!(load(&format!("\u{feff}  \n{chart}")), PhasetidaStatus::Ok);
+}
+
+const HEADER: &str = include_str!("../include/phasetida_core.h");
+const FFI_SOURCE: &str = include_str!("../src/ffi.rs");
+
+/// The `#define`s of the header with a single value
+fn defines() -> HashMap<&'static str, &'static str> {
+    HEADER
+        .lines()
+        .filter_map(|it| it.strip_prefix("#define "))
+        .filter_map(|it| it.split_once(' '))
+        .collect()
+}
+
+fn define(name: &str) -> i64 {
+    defines()[name].parse().unwrap()
+}
+
+/// The fields of a struct of the header as name, schema type and length
+fn c_struct(name: &str) -> Vec<(String, &'static str, usize)> {
+    let start = HEADER.find(&format!("typedef struct {name} {{")).unwrap();
+    let end = start + HEADER[start..].find(&format!("}} {name};")).unwrap();
+    HEADER[start..end]
+        .lines()
+        .skip(1)
+        .map(str::trim)
+        .filter(|it| !it.is_empty() && !it.starts_with("/*"))
+        .map(|line| {
+            let (c_type, field) = line.trim_end_matches(';').split_once(' ').unwrap();
+            let (field, len) = match field.split_once('[') {
+                Some((field, len)) => {
+                    let len = len.trim_end_matches(']');
+                    let len = len.parse().unwrap_or_else(|_| define(len) as usize);
+                    (field, len)
+                }
+                None => (field, 1),
+            };
+            let schema_type = match c_type {
+                "uint8_t" => "u8",
+                "uint16_t" => "u16",
+                "uint32_t" => "u32",
+                "int32_t" => "i32",
+                "double" => "f64",
+                _ => panic!("unknown type {c_type}"),
+            };
+            (field.to_string(), schema_type, len)
+        })
+        .collect()
+}
+
+#[test]
+fn header_declares_every_ffi_function() {
+    let declared = HEADER
+        .lines()
+        .filter(|it| it.starts_with("PhasetidaStatus ") || it.starts_with("uint16_t "))
+        .map(|it| &it[it.find("phasetida_").unwrap()..it.find('(').unwrap()])
+        .collect::<BTreeSet<_>>();
+    let defined = FFI_SOURCE
+        .match_indices("extern \"C\" fn ")
+        .map(|(i, it)| {
+            let name = &FFI_SOURCE[i + it.len()..];
+            &name[..name.find('(').unwrap()]
+        })
+        .collect::<BTreeSet<_>>();
+    assert_eq!(declared, defined);
+}
+
+#[test]
+fn header_constants_match_the_crate() {
+    assert_eq!(
+        define("PHASETIDA_RENDER_PROTOCOL_VERSION"),
+        i64::from(RENDER_PROTOCOL_VERSION)
+    );
+    assert_eq!(
+        RENDER_PROTOCOL_VERSION,
+        ffi::phasetida_render_protocol_version()
+    );
+    assert_eq!(
+        define("PHASETIDA_RECORD_TYPE_COUNT"),
+        RECORD_TYPE_COUNT as i64
+    );
+    assert_eq!(
+        defines()["PHASETIDA_RENDER_MAGIC"],
+        format!("\"{}\"", std::str::from_utf8(&RENDER_MAGIC).unwrap())
+    );
+    for (name, status) in [
+        ("PHASETIDA_OK", PhasetidaStatus::Ok),
+        ("PHASETIDA_NULL_POINTER", PhasetidaStatus::NullPointer),
+        ("PHASETIDA_INVALID_UTF8", PhasetidaStatus::InvalidUtf8),
+        ("PHASETIDA_INVALID_JSON", PhasetidaStatus::InvalidJson),
+        (
+            "PHASETIDA_UNSUPPORTED_VERSION",
+            PhasetidaStatus::UnsupportedVersion,
+        ),
+        ("PHASETIDA_INVALID_CHART", PhasetidaStatus::InvalidChart),
+        ("PHASETIDA_INVALID_PEC", PhasetidaStatus::InvalidPec),
+        (
+            "PHASETIDA_BUFFER_TOO_SMALL",
+            PhasetidaStatus::BufferTooSmall,
+        ),
+        ("PHASETIDA_PANIC", PhasetidaStatus::Panic),
+        (
+            "PHASETIDA_INVALID_ARGUMENT",
+            PhasetidaStatus::InvalidArgument,
+        ),
+    ] {
+        assert_eq!(define(name), status as i64, "{name}");
+    }
+}
+
+#[test]
+fn header_structs_match_the_rust_layout() {
+    let metadata = c_struct("PhasetidaMetadata");
+    let align = metadata.iter().map(|it| type_size(it.1)).max().unwrap();
+    let size = metadata.iter().fold(0usize, |offset, (_, it, len)| {
+        offset.next_multiple_of(type_size(it)) + type_size(it) * len
+    });
+    assert_eq!(
+        size.next_multiple_of(align),
+        size_of::<ffi::PhasetidaMetadata>()
+    );
+
+    let (header, records) = render_schema();
+    assert_eq!(c_struct("PhasetidaFrameHeader"), header);
+
+    let record_sizes = records
+        .iter()
+        .map(|(_, _, fields)| 1 + packed_size(fields))
+        .collect::<Vec<_>>();
+    assert_eq!(record_sizes.len(), RECORD_TYPE_COUNT);
+
+    assert_eq!(
+        load(&chart_json(&[(TAP, 64, 0.0, 0.0)])),
+        PhasetidaStatus::Ok
+    );
+    assert_eq!(
+        ffi::phasetida_tick(0.5, 1.0 / 60.0, false),
+        PhasetidaStatus::Ok
+    );
+    assert_eq!(
+        ffi::phasetida_touch_down(0, 960.0, 540.0),
+        PhasetidaStatus::Ok
+    );
+    let mut frame = vec![0; 1 << 16];
+    let mut len = 0;
+    let status = unsafe { ffi::phasetida_render(frame.as_mut_ptr(), frame.len(), &raw mut len) };
+    assert_eq!(status, PhasetidaStatus::Ok);
+    let Some(Ok(RenderCommand::Header(frame_header))) = decode(&frame[..len]).next() else {
+        panic!("the frame has no header");
+    };
+    assert!(frame_header.counts.iter().filter(|it| **it > 0).count() >= 3);
+    let records = frame_header
+        .counts
+        .iter()
+        .zip(&record_sizes)
+        .map(|(count, size)| *count as usize * size)
+        .sum::<usize>();
+    assert_eq!(len, packed_size(&header) + records + 1);
+}
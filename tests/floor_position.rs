@@ -0,0 +1,46 @@
+mod common;
+
+use common::{DELTA, TAP, line_json, lines_json};
+use phasetida_core::{ChartRaw, Engine};
+
+/// Speed 1 until 1 s, then speed 2
+const SPEEDS: &[common::Speed] = &[(0.0, 64.0, 1.0), (64.0, 1e9, 2.0)];
+
+/// An official chart with taps on a line whose speed doubles at 1 s, with the
+/// floor positions the official charts store
+fn chart(notes: &[common::Note]) -> ChartRaw {
+    ChartRaw::from_json(&lines_json(&[line_json(notes, SPEEDS)])).unwrap()
+}
+
+fn note_y(time: f64) -> f32 {
+    let mut engine = common::engine(chart(&[(TAP, 80, 0.0, 0.0)]));
+    engine.tick_all(time, DELTA, false);
+    let frame = engine.render_frame();
+    assert_eq!(frame.notes.len(), 1, "the tap is not drawn at {time}");
+    frame.notes[0].y
+}
+
+#[test]
+fn note_does_not_jump_at_speed_event_boundary() {
+    let (before, at, after) = (note_y(1.0 - 1e-6), note_y(1.0), note_y(1.0 + 1e-6));
+    assert!((at - before).abs() < 0.01, "{before} -> {at}");
+    assert!((after - at).abs() < 0.01, "{at} -> {after}");
+}
+
+#[test]
+fn recomputed_floor_positions_match_an_official_chart() {
+    let notes = [
+        (TAP, 32, 0.0, 0.0),
+        (TAP, 64, 0.0, 0.0),
+        (TAP, 80, 0.0, 0.0),
+    ];
+    let mut engine = Engine::new();
+    let metadata = engine.try_init(chart(&notes)).unwrap();
+    assert!(metadata.diagnostics.is_empty());
+    engine.set_recompute_floor_position(true);
+    let metadata = engine.try_init(chart(&notes)).unwrap();
+    let report = metadata.floor_position_report.unwrap();
+    assert_eq!(report.recomputed, 3);
+    assert!(report.changes.is_empty(), "{:?}", report.changes);
+    assert!(report.max_delta < 1e-9);
+}
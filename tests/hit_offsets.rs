@@ -0,0 +1,69 @@
+mod common;
+
+use common::{DELTA, DRAG, FLICK, TAP, at, autoplay, chart, engine, play};
+use phasetida_core::{ChartRaw, ComboStatus, Engine, TimingCount};
+
+/// A tap at 0.5 s, a drag at 1 s and a flick at 1.5 s, all at the center
+fn chart_with_every_kind() -> ChartRaw {
+    chart(&[
+        (TAP, 32, 0.0, 0.0),
+        (DRAG, 64, 0.0, 0.0),
+        (FLICK, 96, 0.0, 0.0),
+    ])
+}
+
+fn assert_timing(timing: &TimingCount, early: u32, late: u32) {
+    assert_eq!((timing.early, timing.late), (early, late));
+}
+
+/// Tap 65 ticks in, touch the drag 100 ms early and flick 50 ms early
+fn played() -> Engine {
+    let mut engine = engine(chart_with_every_kind());
+    play(&mut engine, 0.0, 2.0, |engine, time| {
+        if at(time, 0.54) || at(time, 0.9) {
+            engine.set_touch_down(0, 960.0, 540.0);
+        }
+        if at(time, 0.6) || at(time, 1.1) {
+            engine.set_touch_up(0);
+        }
+        if at(time, 1.45) {
+            engine.set_touch_down(1, 960.0, 540.0);
+        }
+        if (1.45 + DELTA..1.5).contains(&time) {
+            engine.set_touch_move(1, 960.0 + ((time - 1.45) * 4000.0) as f32, 540.0);
+        }
+        if at(time, 1.6) {
+            engine.set_touch_up(1);
+        }
+    });
+    engine
+}
+
+#[test]
+fn drag_and_flick_have_no_offset() {
+    let engine = played();
+    let statistics = engine.chart_statistics();
+    assert_eq!(statistics.max_combo, 3);
+    assert!(matches!(statistics.combo_status, ComboStatus::AllPerfect));
+    let tap_offset = (65.0 * DELTA - 0.5) * 1000.0;
+    let offsets = engine.hit_offsets();
+    assert_eq!(offsets.len(), 1);
+    assert!((offsets[0] - tap_offset).abs() < 1e-6, "{offsets:?}");
+    assert_timing(&statistics.perfect_timing, 0, 1);
+    assert_timing(&statistics.good_timing, 0, 0);
+    assert_timing(&statistics.bad_timing, 0, 0);
+    assert!((statistics.mean_offset - tap_offset).abs() < 1e-6);
+    assert!(statistics.offset_std_dev.abs() < 1e-6);
+}
+
+#[test]
+fn autoplay_has_no_offset_bias() {
+    let mut engine = engine(chart_with_every_kind());
+    autoplay(&mut engine, 2.0);
+    let statistics = engine.chart_statistics();
+    assert_eq!(statistics.max_combo, 3);
+    assert_eq!(engine.hit_offsets(), [0.0]);
+    assert_timing(&statistics.perfect_timing, 0, 0);
+    assert_eq!(statistics.mean_offset, 0.0);
+    assert_eq!(statistics.offset_std_dev, 0.0);
+}
@@ -0,0 +1,75 @@
+mod common;
+
+use common::{DELTA, TAP, chart, engine};
+use phasetida_core::{JudgeConfig, NoteScore};
+
+/// 50 ms perfect, 100 ms good and 150 ms bad early, and 30 ms, 60 ms and
+/// 90 ms late
+const CUSTOM: JudgeConfig = JudgeConfig::asymmetric((0.05, 0.1, 0.15), (0.03, 0.06, 0.09));
+
+/// The grade of a tap at 0.5 s hit `delta` seconds late, none if the hit is
+/// too early to judge the tap
+fn grade(config: JudgeConfig, delta: f64) -> Option<NoteScore> {
+    let mut engine = engine(chart(&[(TAP, 32, 0.0, 0.0)]));
+    engine.set_judge_config(config);
+    engine.set_collect_judge_events(true);
+    engine.tick_all(0.5 + delta - DELTA, DELTA, false);
+    engine.set_touch_down(0, 960.0, 540.0);
+    engine.tick_all(0.5 + delta, DELTA, false);
+    let events = engine.drain_judge_events();
+    assert!(events.len() <= 1);
+    events.first().map(|it| it.grade)
+}
+
+#[test]
+fn custom_windows_grade_early_hits() {
+    for (delta, expected) in [
+        (-0.049, Some(NoteScore::Perfect)),
+        (-0.051, Some(NoteScore::Good)),
+        (-0.099, Some(NoteScore::Good)),
+        (-0.101, Some(NoteScore::Bad)),
+        (-0.149, Some(NoteScore::Bad)),
+        (-0.151, None),
+    ] {
+        assert_eq!(grade(CUSTOM, delta), expected, "{delta}");
+    }
+}
+
+#[test]
+fn custom_windows_grade_late_hits() {
+    for (delta, expected) in [
+        (0.029, NoteScore::Perfect),
+        (0.031, NoteScore::Good),
+        (0.059, NoteScore::Good),
+        (0.061, NoteScore::Bad),
+        (0.089, NoteScore::Bad),
+        (0.091, NoteScore::Miss),
+    ] {
+        assert_eq!(grade(CUSTOM, delta), Some(expected), "{delta}");
+    }
+}
+
+#[test]
+fn presets_grade_at_their_boundaries() {
+    for (config, perfect, good, bad) in [
+        (JudgeConfig::OFFICIAL, 0.08, 0.16, 0.18),
+        (JudgeConfig::STRICT, 0.04, 0.075, 0.14),
+        (JudgeConfig::LENIENT, 0.12, 0.24, 0.27),
+    ] {
+        for sign in [-1.0, 1.0] {
+            let at = |seconds: f64, margin: f64| grade(config, sign * (seconds + margin));
+            assert_eq!(at(perfect, -0.001), Some(NoteScore::Perfect));
+            assert_eq!(at(perfect, 0.001), Some(NoteScore::Good));
+            assert_eq!(at(good, -0.001), Some(NoteScore::Good));
+            assert_eq!(at(good, 0.001), Some(NoteScore::Bad));
+            assert_eq!(at(bad, -0.001), Some(NoteScore::Bad));
+            let outside = if sign > 0.0 {
+                Some(NoteScore::Miss)
+            } else {
+                None
+            };
+            assert_eq!(at(bad, 0.001), outside);
+        }
+    }
+    assert_eq!(JudgeConfig::default(), JudgeConfig::OFFICIAL);
+}
@@ -0,0 +1,33 @@
+mod common;
+
+use common::{TAP, autoplay, engine};
+use phasetida_core::{ChartRaw, JudgeEventKind};
+
+/// Two taps, at 0.5 s and 1 s
+fn chart() -> ChartRaw {
+    common::chart(&[(TAP, 32, 0.0, 0.0), (TAP, 64, 0.0, 0.0)])
+}
+
+#[test]
+fn judge_events_are_not_kept_by_default() {
+    let mut engine = engine(chart());
+    autoplay(&mut engine, 1.5);
+    assert_eq!(engine.chart_statistics().max_combo, 2);
+    assert!(engine.drain_judge_events().is_empty());
+}
+
+#[test]
+fn judge_events_are_kept_once_collected() {
+    let mut engine = engine(chart());
+    engine.set_collect_judge_events(true);
+    autoplay(&mut engine, 1.5);
+    let events = engine.drain_judge_events();
+    assert_eq!(events.len(), 2);
+    assert!(events.iter().all(|it| it.kind == JudgeEventKind::Hit));
+    assert!(engine.drain_judge_events().is_empty());
+
+    engine.clear();
+    engine.try_init(chart()).unwrap();
+    autoplay(&mut engine, 1.5);
+    assert_eq!(engine.drain_judge_events().len(), 2);
+}
@@ -0,0 +1,39 @@
+mod common;
+
+use std::collections::BTreeSet;
+
+use common::{CONSTANT_SPEED, DELTA, TAP, autoplay, line_json, lines_json};
+use phasetida_core::{ChartRaw, init_line_states_from_json, render_frame, tick_all};
+
+/// More lines than the 50 the states once had room for
+const LINE_COUNT: usize = 80;
+
+/// Every line has a tap at 1 s, each at a position x of its own
+fn chart_json() -> String {
+    let lines = (0..LINE_COUNT)
+        .map(|i| line_json(&[(TAP, 64, i as f64 * 0.1 - 4.0, 0.0)], CONSTANT_SPEED))
+        .collect::<Vec<_>>();
+    lines_json(&lines)
+}
+
+#[test]
+fn every_line_of_a_large_chart_is_rendered() {
+    init_line_states_from_json(&chart_json()).unwrap();
+    tick_all(0.5, DELTA, false);
+    let frame = render_frame();
+    assert_eq!(frame.lines.len(), LINE_COUNT);
+    assert_eq!(frame.notes.len(), LINE_COUNT);
+    let positions = frame
+        .notes
+        .iter()
+        .map(|it| it.x.to_bits())
+        .collect::<BTreeSet<_>>();
+    assert_eq!(positions.len(), LINE_COUNT);
+}
+
+#[test]
+fn every_line_of_a_large_chart_is_judged() {
+    let mut engine = common::engine(ChartRaw::from_json(&chart_json()).unwrap());
+    autoplay(&mut engine, 1.5);
+    assert_eq!(engine.chart_statistics().max_combo, LINE_COUNT as u32);
+}
@@ -0,0 +1,64 @@
+mod common;
+
+use common::{TAP, at, chart, engine, play};
+use phasetida_core::{Engine, PlayMode};
+
+/// Taps at the center at 0.5 s, 1 s and 1.5 s. The first one is hit 100 ms
+/// late, which is good, the second one is missed and the last one is hit on
+/// time.
+fn played(mode: PlayMode) -> Engine {
+    let mut engine = engine(chart(&[
+        (TAP, 32, 0.0, 0.0),
+        (TAP, 64, 0.0, 0.0),
+        (TAP, 96, 0.0, 0.0),
+    ]));
+    engine.set_play_mode(mode);
+    play(&mut engine, 0.0, 2.0, |engine, time| {
+        if at(time, 0.6) || at(time, 1.5) {
+            engine.set_touch_down(0, 960.0, 540.0);
+        }
+        if at(time, 0.65) || at(time, 1.55) {
+            engine.set_touch_up(0);
+        }
+    });
+    engine
+}
+
+#[test]
+fn normal_run_never_fails() {
+    let engine = played(PlayMode::Normal);
+    let statistics = engine.chart_statistics();
+    assert!(statistics.failed_at.is_none());
+    assert_eq!((statistics.combo, statistics.max_combo), (1, 1));
+    assert_eq!(statistics.good_timing.late, 1);
+    assert_eq!(engine.hit_offsets().len(), 2);
+}
+
+#[test]
+fn sudden_death_ends_the_run_on_the_first_miss() {
+    let engine = played(PlayMode::SuddenDeath);
+    let statistics = engine.chart_statistics();
+    let failed_at = statistics.failed_at.unwrap();
+    assert!((1.18..1.2).contains(&failed_at), "{failed_at}");
+    assert_eq!((statistics.combo, statistics.max_combo), (0, 1));
+    assert_eq!(engine.hit_offsets().len(), 1);
+    assert_eq!(engine.render_frame().statistics.failed_at, Some(failed_at));
+}
+
+#[test]
+fn perfect_only_ends_the_run_on_the_first_good() {
+    let engine = played(PlayMode::PerfectOnly);
+    let statistics = engine.chart_statistics();
+    let failed_at = statistics.failed_at.unwrap();
+    assert!((0.6..0.61).contains(&failed_at), "{failed_at}");
+    assert_eq!((statistics.combo, statistics.max_combo), (1, 1));
+}
+
+#[test]
+fn no_fail_flags_the_run_and_keeps_judging() {
+    let engine = played(PlayMode::NoFail);
+    let statistics = engine.chart_statistics();
+    assert!(statistics.failed_at.is_some());
+    assert_eq!((statistics.combo, statistics.max_combo), (1, 1));
+    assert_eq!(engine.hit_offsets().len(), 2);
+}
@@ -0,0 +1,72 @@
+mod common;
+
+use common::{TAP, autoplay, chart, engine};
+use phasetida_core::{ChartResult, RKS_MIN_ACCURACY, RksFormula, player_rks, single_chart_rks};
+
+fn result(constant: f64, accuracy: f64) -> ChartResult {
+    ChartResult {
+        constant,
+        accuracy,
+        all_perfect: accuracy >= 1.0,
+    }
+}
+
+#[test]
+fn accuracy_below_70_percent_gives_nothing() {
+    assert_eq!(single_chart_rks(15.0, 0.0), 0.0);
+    assert_eq!(single_chart_rks(15.0, RKS_MIN_ACCURACY - 1e-9), 0.0);
+    let at_minimum = single_chart_rks(15.0, RKS_MIN_ACCURACY);
+    assert!((at_minimum - 15.0 / 9.0).abs() < 1e-9, "{at_minimum}");
+}
+
+#[test]
+fn full_accuracy_gives_the_chart_constant() {
+    for constant in [1.0, 12.7, 16.4] {
+        assert_eq!(single_chart_rks(constant, 1.0), constant);
+        assert_eq!(result(constant, 1.0).rks(), constant);
+    }
+    let rks = single_chart_rks(16.0, 0.99);
+    assert!(
+        (rks - 16.0 * (44.0f64 / 45.0).powi(2)).abs() < 1e-9,
+        "{rks}"
+    );
+}
+
+#[test]
+fn player_rks_averages_the_best_and_the_all_perfect_results() {
+    let results = [
+        result(15.0, 1.0),
+        result(16.0, 0.99),
+        result(14.0, 1.0),
+        result(10.0, 0.5),
+    ];
+    let summary = player_rks(
+        &results,
+        RksFormula {
+            best: 2,
+            all_perfect: 1,
+        },
+    );
+    assert_eq!(summary.best, [1, 0]);
+    assert_eq!(summary.all_perfect, [0]);
+    let expected = (results[1].rks() + 15.0 + 15.0) / 3.0;
+    assert!((summary.rks - expected).abs() < 1e-9, "{}", summary.rks);
+}
+
+#[test]
+fn missing_results_count_as_nothing() {
+    let summary = player_rks(&[result(12.0, 1.0)], RksFormula::B27_P3);
+    assert_eq!(summary.best, [0]);
+    assert_eq!(summary.all_perfect, [0]);
+    assert!((summary.rks - 24.0 / 30.0).abs() < 1e-9);
+    assert_eq!(player_rks(&[], RksFormula::B19_P1).rks, 0.0);
+}
+
+#[test]
+fn result_of_an_all_perfect_play() {
+    let mut engine = engine(chart(&[(TAP, 32, 0.0, 0.0)]));
+    autoplay(&mut engine, 1.0);
+    let result = ChartResult::from_statistics(13.5, engine.chart_statistics());
+    assert!(result.all_perfect);
+    assert_eq!(result.rks(), 13.5);
+}
@@ -0,0 +1,165 @@
+mod common;
+
+use common::{HOLD, TAP, autoplay, chart, encode};
+use phasetida_core::{
+    Engine,
+    renders::{DecodeError, Frame, RENDER_PROTOCOL_VERSION, RenderCommand, decode},
+};
+
+/// The length of the frame header
+const HEADER_LEN: usize = 4 + 2 + 1 + 8 + 4 * 7;
+
+/// An engine just after a tap at 1 s was hit by autoplay, with a hold still
+/// to come and a finger on the screen
+fn engine() -> Engine {
+    let mut engine = common::engine(chart(&[(TAP, 64, 0.0, 0.0), (HOLD, 80, 2.0, 32.0)]));
+    autoplay(&mut engine, 1.03);
+    engine.set_touch_down(0, 100.0, 200.0);
+    engine
+}
+
+/// The commands a frame is expected to decode to, in the order they are
+/// written
+fn commands(frame: &Frame) -> Vec<RenderCommand> {
+    let mut commands = vec![RenderCommand::Statistics {
+        combo: frame.statistics.combo,
+        max_combo: frame.statistics.max_combo,
+        score: frame.statistics.score as f32,
+        accurate: frame.statistics.accurate as f32,
+        rank: frame.statistics.rank as u8,
+        combo_status: frame.statistics.combo_status as u8,
+        failed: 0,
+        failed_at: 0.0,
+    }];
+    commands.extend(frame.lines.iter().map(|it| RenderCommand::Line {
+        x1: it.x1,
+        y1: it.y1,
+        x2: it.x2,
+        y2: it.y2,
+        alpha: it.alpha,
+        color: it.color,
+    }));
+    commands.extend(frame.notes.iter().map(|it| RenderCommand::Note {
+        note_type: it.note_type,
+        x: it.x,
+        y: it.y,
+        rotate: it.rotate,
+        height: it.height,
+        high_light: i8::from(it.high_light),
+    }));
+    commands.extend(
+        frame
+            .click_effects
+            .iter()
+            .map(|it| RenderCommand::ClickEffect {
+                x: it.x,
+                y: it.y,
+                frame: it.frame,
+                tint_type: it.tint_type,
+            }),
+    );
+    commands.extend(
+        frame
+            .splash_effects
+            .iter()
+            .map(|it| RenderCommand::SplashEffect {
+                x: it.x,
+                y: it.y,
+                frame: it.frame,
+                tint_type: it.tint_type,
+            }),
+    );
+    commands.push(RenderCommand::Sound {
+        tap_sound: frame.sound.tap_sound,
+        drag_sound: frame.sound.drag_sound,
+        flick_sound: frame.sound.flick_sound,
+    });
+    commands.extend(
+        frame
+            .touches
+            .iter()
+            .map(|it| RenderCommand::Point { x: it.x, y: it.y }),
+    );
+    commands
+}
+
+#[test]
+fn decoded_frame_matches_the_rendered_frame() {
+    let engine = engine();
+    let frame = engine.render_frame();
+    let mut decoded = decode(&encode(&engine))
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    let RenderCommand::Header(header) = decoded.remove(0) else {
+        panic!("the frame does not start with a header");
+    };
+    assert_eq!(header.version, RENDER_PROTOCOL_VERSION);
+    assert!(!header.big_endian);
+    assert_eq!(header.frame_time, frame.frame_time);
+    assert_eq!(
+        header.counts,
+        [
+            frame.lines.len(),
+            frame.notes.len(),
+            frame.click_effects.len(),
+            frame.touches.len(),
+            1,
+            frame.splash_effects.len(),
+            1,
+        ]
+        .map(|it| it as u32)
+    );
+    for (count, name) in header.counts.iter().zip(["line", "note", "click", "touch"]) {
+        assert!(*count > 0, "the frame has no {name} record");
+    }
+    assert_eq!(decoded, commands(&frame));
+}
+
+#[test]
+fn truncated_frame_is_an_error() {
+    let bytes = encode(&engine());
+    let last = decode(&bytes[..bytes.len() - 1]).last().unwrap();
+    assert_eq!(
+        last,
+        Err(DecodeError::Truncated {
+            offset: bytes.len() - 1
+        })
+    );
+    let last = decode(&bytes[..HEADER_LEN + 3]).last().unwrap();
+    assert_eq!(last, Err(DecodeError::Truncated { offset: HEADER_LEN }));
+    let first = decode(&bytes[..HEADER_LEN - 1]).next().unwrap();
+    assert_eq!(first, Err(DecodeError::Truncated { offset: 0 }));
+}
+
+#[test]
+fn unknown_record_is_an_error() {
+    let mut bytes = encode(&engine());
+    let offset = bytes.len() - 1;
+    bytes[offset] = 9;
+    let last = decode(&bytes).last().unwrap();
+    assert_eq!(
+        last,
+        Err(DecodeError::UnknownRecord {
+            offset,
+            record_type: 9
+        })
+    );
+    assert_eq!(decode(&bytes).filter(Result::is_err).count(), 1);
+}
+
+#[test]
+fn bad_magic_and_version_are_errors() {
+    let mut bytes = encode(&engine());
+    bytes[4..6].copy_from_slice(&(RENDER_PROTOCOL_VERSION + 1).to_le_bytes());
+    assert_eq!(
+        decode(&bytes).collect::<Vec<_>>(),
+        [Err(DecodeError::UnsupportedVersion(
+            RENDER_PROTOCOL_VERSION + 1
+        ))]
+    );
+    bytes[0] = b'X';
+    assert_eq!(
+        decode(&bytes).collect::<Vec<_>>(),
+        [Err(DecodeError::BadMagic)]
+    );
+}
@@ -0,0 +1,130 @@
+mod common;
+
+use common::{
+    CONSTANT_SPEED, DELTA, TAP, at, autoplay, chart, engine, line_json, lines_json, play,
+};
+use phasetida_core::{
+    ChartRaw, ComboStatus, Engine,
+    renders::{LineDraw, NoteDraw, SoundDraw},
+};
+use serde_json::json;
+
+#[test]
+fn frame_holds_what_is_on_screen() {
+    let mut engine = engine(chart(&[(TAP, 64, 0.0, 0.0)]));
+    autoplay(&mut engine, 0.5);
+    let frame = engine.render_frame();
+    assert_eq!(frame.frame_time, 0.5);
+    assert_eq!(
+        frame.lines,
+        [LineDraw {
+            x1: 1920.0,
+            y1: 540.0,
+            x2: 0.0,
+            y2: 540.0,
+            alpha: 1.0,
+            color: [0xfe, 0xff, 0xa9],
+        }]
+    );
+    assert_eq!(
+        frame.notes,
+        [NoteDraw {
+            note_type: 1,
+            x: 960.0,
+            y: 216.0,
+            rotate: 360.0,
+            height: 0.0,
+            high_light: false,
+        }]
+    );
+    assert!(frame.click_effects.is_empty());
+    assert_eq!(frame.sound, SoundDraw::default());
+
+    autoplay(&mut engine, 1.05);
+    let frame = engine.render_frame();
+    assert!(frame.notes.is_empty());
+    assert_eq!(frame.click_effects.len(), 1);
+    assert_eq!(frame.click_effects[0].tint_type, 0);
+    assert_eq!(frame.statistics.max_combo, 1);
+}
+
+#[test]
+fn frame_serializes_to_json() {
+    let mut engine = engine(chart(&[(TAP, 64, 0.0, 0.0)]));
+    autoplay(&mut engine, 0.5);
+    engine.set_touch_down(0, 100.0, 200.0);
+    let value = serde_json::to_value(engine.render_frame()).unwrap();
+    assert_eq!(value["frame_time"], json!(0.5));
+    assert_eq!(
+        value["lines"],
+        json!([{"x1": 1920.0, "y1": 540.0, "x2": 0.0, "y2": 540.0, "alpha": 1.0, "color": [254, 255, 169]}])
+    );
+    assert_eq!(
+        value["notes"],
+        json!([{"note_type": 1, "x": 960.0, "y": 216.0, "rotate": 360.0, "height": 0.0, "high_light": false}])
+    );
+    assert_eq!(value["touches"], json!([{"x": 100.0, "y": 200.0}]));
+    assert_eq!(
+        value["sound"],
+        json!({"tap_sound": 0, "drag_sound": 0, "flick_sound": 0})
+    );
+    assert_eq!(value["click_effects"], json!([]));
+    assert_eq!(value["splash_effects"], json!([]));
+    assert_eq!(value["statistics"]["combo_status"], json!("AllPerfect"));
+}
+
+/// The colour of every line
+fn line_colors(engine: &Engine) -> Vec<[u8; 3]> {
+    engine
+        .render_frame()
+        .lines
+        .iter()
+        .map(|it| it.color)
+        .collect()
+}
+
+#[test]
+fn lines_are_tinted_by_the_combo_status() {
+    let line = line_json(&[(TAP, 32, 0.0, 0.0), (TAP, 64, 0.0, 0.0)], CONSTANT_SPEED);
+    let mut engine = engine(ChartRaw::from_json(&lines_json(&[line.clone(), line])).unwrap());
+    let gold = ComboStatus::AllPerfect.line_color();
+    let blue = ComboStatus::FullCombo.line_color();
+    let white = ComboStatus::None.line_color();
+    let mut colors = Vec::new();
+    // both taps at 0.5 s are hit 120 ms late for goods, those at 1 s are missed
+    play(&mut engine, 0.0, 1.5, |engine, time| {
+        if at(time, 0.41) || at(time, 0.81) || at(time, 1.41) {
+            colors.push(line_colors(engine));
+        }
+        if at(time, 0.62) {
+            engine.set_touch_down(0, 960.0, 540.0);
+            engine.set_touch_down(1, 960.0, 540.0);
+        }
+        if at(time, 0.7) {
+            engine.set_touch_up(0);
+            engine.set_touch_up(1);
+        }
+    });
+    assert_eq!(colors, [[gold; 2], [blue; 2], [white; 2]]);
+}
+
+#[test]
+fn line_color_overrides_the_tint() {
+    let line = line_json(&[(TAP, 32, 0.0, 0.0)], CONSTANT_SPEED);
+    let mut engine = engine(ChartRaw::from_json(&lines_json(&[line.clone(), line])).unwrap());
+    let gold = ComboStatus::AllPerfect.line_color();
+    let red = [0xff, 0, 0];
+    engine.tick_all(0.0, DELTA, false);
+    engine.set_line_color(1, Some(red));
+    assert_eq!(line_colors(&engine), [gold, red]);
+
+    play(&mut engine, 0.0, 1.0, |_, _| {});
+    assert_eq!(engine.chart_statistics().combo_status, ComboStatus::None);
+    assert_eq!(line_colors(&engine), [ComboStatus::None.line_color(), red]);
+
+    engine.set_line_color(1, None);
+    assert_eq!(line_colors(&engine), [ComboStatus::None.line_color(); 2]);
+    // a line that does not exist is ignored
+    engine.set_line_color(2, Some(red));
+    assert_eq!(engine.render_frame().lines.len(), 2);
+}
@@ -0,0 +1,84 @@
+mod common;
+
+use std::collections::HashMap;
+
+use common::{HOLD, TAP, autoplay, chart, encode, render_schema, type_size};
+use phasetida_core::{Engine, RENDER_MAGIC, RENDER_PROTOCOL_VERSION};
+
+/// A frame with a line, notes, effects and a touch
+fn engine() -> Engine {
+    let mut engine = common::engine(chart(&[(TAP, 64, 0.0, 0.0), (HOLD, 80, 2.0, 32.0)]));
+    autoplay(&mut engine, 1.03);
+    engine.set_touch_down(0, 100.0, 200.0);
+    engine
+}
+
+#[test]
+fn frame_header_and_records_follow_the_schema() {
+    let (header, records) = render_schema();
+    let engine = engine();
+    let bytes = encode(&engine);
+    let frame = engine.render_frame();
+
+    let mut offset = 0;
+    let mut take = |len: usize| {
+        let slice = &bytes[offset..offset + len];
+        offset += len;
+        slice
+    };
+    let mut fields = HashMap::new();
+    for (name, field_type, len) in &header {
+        fields.insert(name.as_str(), take(type_size(field_type) * len));
+    }
+    assert_eq!(fields["magic"], RENDER_MAGIC);
+    assert_eq!(fields["magic"], b"PTDF");
+    assert_eq!(
+        fields["version"],
+        RENDER_PROTOCOL_VERSION.to_ne_bytes().as_slice()
+    );
+    assert_eq!(
+        fields["endianness"],
+        [u8::from(cfg!(target_endian = "big"))]
+    );
+    assert_eq!(
+        fields["frame_time"],
+        frame.frame_time.to_ne_bytes().as_slice()
+    );
+    let counts = fields["counts"]
+        .chunks(4)
+        .map(|it| u32::from_ne_bytes(it.try_into().unwrap()))
+        .collect::<Vec<_>>();
+    assert_eq!(counts.len(), records.len());
+
+    let sizes = records
+        .iter()
+        .map(|(record_type, _, fields)| (*record_type, common::packed_size(fields)))
+        .collect::<HashMap<_, _>>();
+    let mut found = vec![0; records.len()];
+    loop {
+        let record_type = take(1)[0];
+        if record_type == 0 {
+            break;
+        }
+        found[usize::from(record_type) - 1] += 1;
+        take(sizes[&record_type]);
+    }
+    assert_eq!(offset, bytes.len());
+    assert_eq!(found, counts);
+
+    let expected = records
+        .iter()
+        .map(|(_, name, _)| match *name {
+            "line" => frame.lines.len(),
+            "note" => frame.notes.len(),
+            "click_effect" => frame.click_effects.len(),
+            "touch" => frame.touches.len(),
+            "splash_effect" => frame.splash_effects.len(),
+            "statistics" | "sound" => 1,
+            _ => panic!("unknown record {name}"),
+        })
+        .map(|it| it as u32)
+        .collect::<Vec<_>>();
+    assert_eq!(counts, expected);
+    assert!(counts.iter().all(|it| *it > 0), "{counts:?}");
+}
@@ -0,0 +1,141 @@
+mod common;
+
+use common::{DELTA, TAP, at};
+use phasetida_core::{
+    ChartRaw, ComboOrder, Engine, JudgeConfig, PlayMode, Replay, ReplayError, ReplayEvent,
+    SeekPolicy,
+};
+use serde_json::Value;
+
+/// Taps at the center every 0.5 s from 0.5 s to 3 s
+fn chart() -> ChartRaw {
+    common::chart(&(1..=6).map(|i| (TAP, i * 32, 0.0, 0.0)).collect::<Vec<_>>())
+}
+
+fn engine() -> Engine {
+    common::engine(chart())
+}
+
+/// Tick from `from` to `to` seconds, tapping every note 60 ms late and
+/// changing the settings on the way
+fn play(engine: &mut Engine, from: f64, to: f64) {
+    common::play(engine, from, to, |engine, time| {
+        let since_note = (time - 0.06) % 0.5;
+        if time > 0.4 && since_note < DELTA {
+            engine.set_touch_down(0, 960.0, 540.0);
+        } else if time > 0.4 && at(since_note, 0.05) {
+            engine.set_touch_up(0);
+        }
+        if at(time, 1.2) {
+            engine.set_judge_config(JudgeConfig::STRICT);
+        }
+        if at(time, 2.2) {
+            engine.set_combo_order(ComboOrder::EndTime);
+        }
+        if at(time, 2.7) {
+            engine.set_play_mode(PlayMode::NoFail);
+        }
+    });
+}
+
+/// Everything a replay must reproduce
+fn outcome(engine: &Engine) -> (Value, Vec<f64>, JudgeConfig, PlayMode, ComboOrder) {
+    (
+        serde_json::to_value(engine.chart_statistics()).unwrap(),
+        engine.hit_offsets(),
+        engine.judge_config(),
+        engine.play_mode(),
+        engine.combo_order(),
+    )
+}
+
+/// Encode and decode the replay, then play it on an engine with other
+/// settings
+fn replayed(replay: &Replay) -> Engine {
+    let decoded = Replay::from_bytes(&replay.to_bytes()).unwrap();
+    assert_eq!(&decoded, replay);
+    let mut engine = engine();
+    engine.set_judge_config(JudgeConfig::LENIENT);
+    engine.set_play_mode(PlayMode::SuddenDeath);
+    engine.play_replay(&decoded);
+    engine
+}
+
+#[test]
+fn replay_reproduces_the_statistics() {
+    let mut recorded = engine();
+    recorded.set_play_mode(PlayMode::PerfectOnly);
+    assert!(recorded.start_recording());
+    play(&mut recorded, 0.0, 3.5);
+    let replay = recorded.stop_recording().unwrap();
+    assert_eq!(replay.play_mode(), PlayMode::PerfectOnly);
+    assert!(
+        replay
+            .events()
+            .contains(&ReplayEvent::SetJudgeConfig(JudgeConfig::STRICT))
+    );
+    let statistics = recorded.chart_statistics();
+    assert!(statistics.failed_at.is_some());
+    assert_eq!(statistics.max_combo, 3);
+    assert_eq!(outcome(&replayed(&replay)), outcome(&recorded));
+}
+
+#[test]
+fn replay_recorded_after_a_seek_starts_from_it() {
+    let mut recorded = engine();
+    play(&mut recorded, 0.0, 1.0);
+    recorded.seek(1.25, SeekPolicy::AutoplayFill);
+    assert!(recorded.start_recording());
+    play(&mut recorded, 1.25, 3.5);
+    let replay = recorded.stop_recording().unwrap();
+    assert_eq!(
+        replay.events()[0],
+        ReplayEvent::Seek {
+            time: 1.25,
+            policy: SeekPolicy::AutoplayFill
+        }
+    );
+    assert_eq!(outcome(&replayed(&replay)), outcome(&recorded));
+}
+
+#[test]
+fn recording_can_not_start_during_a_play() {
+    let mut engine = engine();
+    engine.set_touch_down(0, 960.0, 540.0);
+    assert!(!engine.start_recording());
+    engine.set_touch_up(0);
+    engine.tick_all(0.0, DELTA, false);
+    assert!(!engine.start_recording());
+    assert!(!engine.is_recording());
+
+    let snapshot = engine.snapshot();
+    engine.seek(0.0, SeekPolicy::Reset);
+    assert!(engine.start_recording());
+    engine.restore(&snapshot);
+    assert!(!engine.is_recording());
+}
+
+#[test]
+fn unknown_settings_are_errors() {
+    let mut recorded = engine();
+    assert!(recorded.start_recording());
+    recorded.set_combo_order(ComboOrder::EndTime);
+    let mut bytes = recorded.stop_recording().unwrap().to_bytes();
+    let last = bytes.len() - 1;
+    bytes[last] = 7;
+    assert_eq!(
+        Replay::from_bytes(&bytes),
+        Err(ReplayError::UnknownComboOrder {
+            offset: last,
+            order: 7
+        })
+    );
+    bytes[last - 2] = 9;
+    assert_eq!(
+        Replay::from_bytes(&bytes),
+        Err(ReplayError::UnknownComboOrder {
+            offset: last - 2,
+            order: 9
+        })
+    );
+}
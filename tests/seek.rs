@@ -0,0 +1,99 @@
+mod common;
+
+use common::{HOLD, TAP, at, autoplay, chart, engine, play};
+use phasetida_core::{ComboStatus, Engine, PlayMode, SeekPolicy};
+
+/// Taps at the center at 0.5 s, 1 s, 1.5 s and 2 s
+fn taps() -> Engine {
+    engine(chart(&[
+        (TAP, 32, 0.0, 0.0),
+        (TAP, 64, 0.0, 0.0),
+        (TAP, 96, 0.0, 0.0),
+        (TAP, 128, 0.0, 0.0),
+    ]))
+}
+
+/// Tap every note from `from` seconds on time
+fn tap_from(engine: &mut Engine, from: f64) {
+    play(engine, from, 2.5, |engine, time| {
+        if [0.5, 1.0, 1.5, 2.0].iter().any(|it| at(time, *it)) {
+            engine.set_touch_down(0, 960.0, 540.0);
+        }
+        if [0.55, 1.05, 1.55, 2.05].iter().any(|it| at(time, *it)) {
+            engine.set_touch_up(0);
+        }
+    });
+}
+
+#[test]
+fn practice_excludes_the_skipped_notes_from_the_statistics() {
+    let mut engine = taps();
+    engine.seek(1.25, SeekPolicy::Practice);
+    let statistics = engine.chart_statistics();
+    assert_eq!((statistics.combo, statistics.max_combo), (0, 0));
+    tap_from(&mut engine, 1.25);
+    let statistics = engine.chart_statistics();
+    assert_eq!((statistics.combo, statistics.max_combo), (2, 2));
+    assert_eq!(statistics.accurate, 1.0);
+    assert_eq!(statistics.score, 1_000_000.0);
+    assert!(matches!(statistics.combo_status, ComboStatus::AllPerfect));
+    assert_eq!(engine.hit_offsets().len(), 2);
+}
+
+#[test]
+fn autoplay_fill_judges_the_skipped_notes_as_perfect() {
+    let mut engine = taps();
+    engine.seek(1.25, SeekPolicy::AutoplayFill);
+    let statistics = engine.chart_statistics();
+    assert_eq!((statistics.combo, statistics.max_combo), (2, 2));
+    assert_eq!(statistics.accurate, 0.5);
+    tap_from(&mut engine, 1.25);
+    let statistics = engine.chart_statistics();
+    assert_eq!((statistics.combo, statistics.max_combo), (4, 4));
+    assert_eq!(statistics.score, 1_000_000.0);
+}
+
+#[test]
+fn autoplay_fill_into_a_hold_completes_it() {
+    let mut engine = engine(chart(&[(TAP, 32, 0.0, 0.0), (HOLD, 64, 0.0, 64.0)]));
+    engine.seek(1.5, SeekPolicy::AutoplayFill);
+    assert_eq!(engine.chart_statistics().combo, 1);
+    play(&mut engine, 1.5, 2.5, |engine, time| {
+        if at(time, 1.5) {
+            engine.set_touch_down(0, 960.0, 540.0);
+        }
+    });
+    let statistics = engine.chart_statistics();
+    assert_eq!(statistics.max_combo, 2);
+    assert_eq!(statistics.score, 1_000_000.0);
+}
+
+#[test]
+fn reset_restarts_the_play() {
+    let mut engine = taps();
+    autoplay(&mut engine, 1.25);
+    assert_eq!(engine.chart_statistics().combo, 2);
+    engine.seek(0.0, SeekPolicy::Reset);
+    let statistics = engine.chart_statistics();
+    assert_eq!((statistics.combo, statistics.max_combo), (0, 0));
+    assert_eq!(statistics.accurate, 0.0);
+    tap_from(&mut engine, 0.0);
+    assert_eq!(engine.chart_statistics().max_combo, 4);
+}
+
+#[test]
+fn reset_forward_misses_the_skipped_notes() {
+    let mut engine = taps();
+    engine.set_play_mode(PlayMode::SuddenDeath);
+    engine.seek(1.25, SeekPolicy::Reset);
+    assert!(engine.chart_statistics().failed_at.is_none());
+    engine.tick_all(1.25, 1.0 / 120.0, false);
+    let statistics = engine.chart_statistics();
+    assert_eq!(statistics.max_combo, 0);
+    assert!(statistics.failed_at.is_some());
+
+    engine.seek(1.25, SeekPolicy::Practice);
+    assert!(engine.chart_statistics().failed_at.is_none());
+    tap_from(&mut engine, 1.25);
+    assert!(engine.chart_statistics().failed_at.is_none());
+}
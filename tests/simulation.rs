@@ -0,0 +1,77 @@
+mod common;
+
+use common::{TAP, at, engine, play};
+use phasetida_core::{
+    ChartRaw, JudgeConfig, PlayMode, SimulationError, SimulationInput, SimulationOptions, simulate,
+};
+
+/// Taps at the center at 0.5 s, 1 s and 1.5 s
+fn chart() -> ChartRaw {
+    common::chart(&[
+        (TAP, 32, 0.0, 0.0),
+        (TAP, 64, 0.0, 0.0),
+        (TAP, 96, 0.0, 0.0),
+    ])
+}
+
+#[test]
+fn invalid_tick_rate_is_an_error() {
+    for tick_rate in [0.0, -60.0, f64::INFINITY] {
+        let options = SimulationOptions {
+            tick_rate,
+            ..SimulationOptions::default()
+        };
+        assert_eq!(
+            simulate(chart(), &options).err(),
+            Some(SimulationError::InvalidTickRate(tick_rate))
+        );
+    }
+    let options = SimulationOptions {
+        tick_rate: f64::NAN,
+        ..SimulationOptions::default()
+    };
+    assert!(matches!(
+        simulate(chart(), &options),
+        Err(SimulationError::InvalidTickRate(rate)) if rate.is_nan()
+    ));
+}
+
+#[test]
+fn autoplay_hits_every_note_on_time() {
+    let report = simulate(chart(), &SimulationOptions::default()).unwrap();
+    assert!(report.anomalies.is_empty());
+    assert_eq!(report.statistics.max_combo, 3);
+    assert!(report.notes.iter().all(|it| it.offset_ms == Some(0.0)));
+}
+
+#[test]
+fn replay_is_simulated_with_its_own_settings() {
+    let mut recorded = engine(chart());
+    recorded.set_judge_config(JudgeConfig::STRICT);
+    recorded.set_play_mode(PlayMode::PerfectOnly);
+    assert!(recorded.start_recording());
+    play(&mut recorded, 0.0, 2.0, |engine, time| {
+        if at(time, 0.56) || at(time, 1.56) {
+            engine.set_touch_down(0, 960.0, 540.0);
+        }
+        if at(time, 0.6) || at(time, 1.6) {
+            engine.set_touch_up(0);
+        }
+    });
+    let replay = recorded.stop_recording().unwrap();
+
+    let report = simulate(
+        chart(),
+        &SimulationOptions {
+            input: SimulationInput::Replay(replay),
+            ..SimulationOptions::default()
+        },
+    )
+    .unwrap();
+    assert_eq!(report.tick_count, 241);
+    assert_eq!(
+        serde_json::to_value(&report.statistics).unwrap(),
+        serde_json::to_value(recorded.chart_statistics()).unwrap()
+    );
+    assert!(report.statistics.failed_at.is_some());
+}
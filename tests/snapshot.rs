@@ -0,0 +1,77 @@
+mod common;
+
+use common::{DELTA, HOLD, TAP, at, chart, engine, play};
+use phasetida_core::{ChartRaw, Engine, EngineSnapshot};
+use serde_json::Value;
+
+/// Taps at 0.5 s and 1 s, a hold from 1.5 s to 2 s and a tap at 2.5 s
+fn fixture() -> ChartRaw {
+    chart(&[
+        (TAP, 32, 0.0, 0.0),
+        (TAP, 64, 0.0, 0.0),
+        (HOLD, 96, 0.0, 32.0),
+        (TAP, 160, 0.0, 0.0),
+    ])
+}
+
+/// Tap the notes a little late and keep holding the hold
+fn input(engine: &mut Engine, time: f64) {
+    if at(time, 0.53) || at(time, 1.1) || at(time, 1.52) || at(time, 2.56) {
+        engine.set_touch_down(0, 960.0, 540.0);
+    }
+    if at(time, 0.6) || at(time, 1.15) || at(time, 2.1) || at(time, 2.6) {
+        engine.set_touch_up(0);
+    }
+}
+
+fn outcome(engine: &Engine) -> (Value, Vec<f64>) {
+    (
+        serde_json::to_value(engine.chart_statistics()).unwrap(),
+        engine.hit_offsets(),
+    )
+}
+
+/// Play to the middle of the hold and take a snapshot
+fn snapshot(engine: &mut Engine) -> EngineSnapshot {
+    play(engine, 0.0, 1.75, input);
+    engine.snapshot()
+}
+
+#[test]
+fn restored_json_snapshot_plays_on_identically() {
+    let mut original = engine(fixture());
+    let snapshot = snapshot(&mut original);
+    let json = snapshot.to_json().unwrap();
+    let decoded = EngineSnapshot::from_json(&json).unwrap();
+    assert_eq!(decoded.frame_time(), snapshot.frame_time());
+    assert_eq!(decoded.to_json().unwrap(), json);
+
+    let next = snapshot.frame_time() + DELTA;
+    play(&mut original, next, 3.0, input);
+
+    let mut restored = engine(fixture());
+    restored.restore(&decoded);
+    play(&mut restored, next, 3.0, input);
+    assert_eq!(outcome(&restored), outcome(&original));
+    assert_eq!(restored.chart_statistics().max_combo, 4);
+}
+
+#[test]
+fn restore_rewinds_the_judgements() {
+    let mut engine = engine(fixture());
+    let snapshot = snapshot(&mut engine);
+    let before = outcome(&engine);
+    assert_eq!(engine.chart_statistics().combo, 2);
+
+    play(&mut engine, snapshot.frame_time() + DELTA, 3.0, |_, _| {});
+    assert_eq!(engine.chart_statistics().combo, 0);
+    engine.restore(&snapshot);
+    assert_eq!(outcome(&engine), before);
+    assert_eq!(engine.render_frame().frame_time, snapshot.frame_time());
+}
+
+#[test]
+fn malformed_json_is_an_error() {
+    assert!(EngineSnapshot::from_json("{}").is_err());
+    assert!(EngineSnapshot::from_json("not json").is_err());
+}
@@ -0,0 +1,133 @@
+mod common;
+
+use common::{TAP, at, autoplay, chart, engine, play};
+use phasetida_core::{ComboOrder, ComboStatus, Engine, Rank, TimingCount};
+
+/// The early and late counts
+fn counts(timing: TimingCount) -> (u32, u32) {
+    (timing.early, timing.late)
+}
+
+/// Taps at the center every 0.5 s from 0.5 s to 3 s
+fn fixture() -> Engine {
+    engine(chart(
+        &(1..=6).map(|i| (TAP, i * 32, 0.0, 0.0)).collect::<Vec<_>>(),
+    ))
+}
+
+/// Hit the second tap 120 ms late for a good, miss the fourth and hit the
+/// others 10 ms late, calling `observe` before every tick
+fn play_mixed(engine: &mut Engine, mut observe: impl FnMut(&Engine, f64)) {
+    play(engine, 0.0, 3.5, |engine, time| {
+        observe(engine, time);
+        for (note, offset) in [
+            (0.5, 0.01),
+            (1.0, 0.12),
+            (1.5, 0.01),
+            (2.5, 0.01),
+            (3.0, 0.01),
+        ] {
+            if at(time, note + offset) {
+                engine.set_touch_down(0, 960.0, 540.0);
+            }
+            if at(time, note + offset + 0.05) {
+                engine.set_touch_up(0);
+            }
+        }
+    });
+}
+
+#[test]
+fn statistics_follow_the_judgements() {
+    let mut engine = fixture();
+    play_mixed(&mut engine, |_, _| {});
+    let statistics = engine.chart_statistics();
+    assert_eq!(statistics.combo, 2);
+    assert_eq!(statistics.max_combo, 3);
+    assert_eq!(statistics.accurate, (4.0 + 0.65) / 6.0);
+    assert_eq!(
+        statistics.score,
+        3.0 / 6.0 * 100_000.0 + (4.0 + 0.65) / 6.0 * 900_000.0
+    );
+    assert_eq!(counts(statistics.perfect_timing), (0, 4));
+    assert_eq!(counts(statistics.good_timing), (0, 1));
+    assert_eq!(counts(statistics.bad_timing), (0, 0));
+}
+
+#[test]
+fn incremental_statistics_match_a_full_rescan() {
+    let mut engine = fixture();
+    play_mixed(&mut engine, |_, _| {});
+    let incremental = serde_json::to_value(engine.chart_statistics()).unwrap();
+    // changing the combo order recomputes the statistics from every note
+    engine.set_combo_order(ComboOrder::Judgment);
+    assert_eq!(
+        serde_json::to_value(engine.chart_statistics()).unwrap(),
+        incremental
+    );
+}
+
+#[test]
+fn dense_stream_is_counted_note_by_note() {
+    let mut engine = engine(chart(
+        &(0..500)
+            .map(|i| (TAP, 64 + i, 0.0, 0.0))
+            .collect::<Vec<_>>(),
+    ));
+    autoplay(&mut engine, 1.0 + 500.0 / 64.0);
+    let statistics = engine.chart_statistics();
+    assert_eq!(statistics.combo, 500);
+    assert_eq!(statistics.max_combo, 500);
+    assert_eq!(statistics.accurate, 1.0);
+    assert_eq!(statistics.score, 1_000_000.0);
+}
+
+#[test]
+fn rank_follows_the_score() {
+    for (score, rank, letter) in [
+        (1_000_000.0, Rank::Phi, "φ"),
+        (999_999.0, Rank::V, "V"),
+        (960_000.0, Rank::V, "V"),
+        (959_999.0, Rank::S, "S"),
+        (920_000.0, Rank::S, "S"),
+        (880_000.0, Rank::A, "A"),
+        (820_000.0, Rank::B, "B"),
+        (700_000.0, Rank::C, "C"),
+        (699_999.0, Rank::F, "F"),
+        (0.0, Rank::F, "F"),
+    ] {
+        assert_eq!(Rank::from_score(score), rank, "{score}");
+        assert_eq!(rank.to_string(), letter);
+    }
+}
+
+#[test]
+fn autoplay_is_an_all_perfect_phi() {
+    let mut engine = fixture();
+    autoplay(&mut engine, 3.5);
+    let statistics = engine.chart_statistics();
+    assert_eq!(statistics.rank, Rank::Phi);
+    assert_eq!(statistics.combo_status, ComboStatus::AllPerfect);
+}
+
+#[test]
+fn good_ends_all_perfect_and_miss_ends_full_combo() {
+    let mut engine = fixture();
+    let mut status = Vec::new();
+    play_mixed(&mut engine, |engine, time| {
+        if at(time, 0.8) || at(time, 1.3) || at(time, 2.3) {
+            status.push(engine.chart_statistics().combo_status);
+        }
+    });
+    assert_eq!(
+        status,
+        [
+            ComboStatus::AllPerfect,
+            ComboStatus::FullCombo,
+            ComboStatus::None
+        ]
+    );
+    let statistics = engine.chart_statistics();
+    assert_eq!(statistics.combo_status, ComboStatus::None);
+    assert_eq!(statistics.rank, Rank::C);
+}